@@ -3,7 +3,7 @@ use abi_stable::{
     library::RootModule,
     package_version_strings,
     sabi_types::VersionStrings,
-    std_types::{ROption, RString, RVec},
+    std_types::{ROption, RString, RVec, Tuple2},
     StableAbi,
 };
 
@@ -17,6 +17,19 @@ pub struct Plugin {
     pub get_matches: extern "C" fn(RString) -> u64,
     pub poll_matches: extern "C" fn(u64) -> PollResult,
     pub handle_selection: extern "C" fn(Match) -> HandleResult,
+    /// Called when anyrun goes back from a submenu entered with `HandleResult::Submenu`.
+    pub navigate_back: extern "C" fn(),
+    /// Called on `Tab` with the selected match, can return a replacement for the entry text.
+    pub complete: extern "C" fn(Match) -> ROption<EntryText>,
+    /// Like `handle_selection`, but also gets how the match was activated.
+    pub handle_activation: extern "C" fn(Match, Activation) -> HandleResult,
+    /// Handles a submitted form, with the match of the form and the values of its fields.
+    pub handle_form: extern "C" fn(Match, RVec<FormValue>) -> HandleResult,
+    /// Provides content for the preview pane when a match is selected.
+    pub preview: extern "C" fn(Match) -> ROption<Preview>,
+    /// Extra CSS classes for the row of a match, e.g. `warning` or `urgent`.
+    #[sabi(last_prefix_field)]
+    pub classes: extern "C" fn(Match) -> RVec<RString>,
 }
 
 /// Info of the plugin. Used for the main UI
@@ -24,7 +37,7 @@ pub struct Plugin {
 #[derive(StableAbi, Debug)]
 pub struct PluginInfo {
     pub name: RString,
-    /// The icon name from the icon theme in use
+    /// The icon name from the icon theme in use, an absolute path, or a `file://` or `data:` URI
     pub icon: RString,
 }
 
@@ -39,12 +52,196 @@ pub struct Match {
     pub description: ROption<RString>,
     /// Whether the title and description should be interpreted as pango markup.
     pub use_pango: bool,
-    /// The icon name from the icon theme in use
+    /// The icon name from the icon theme in use, an absolute path, or a `file://` or `data:` URI.
+    /// The icon of the plugin is shown if it can't be found.
     pub icon: ROption<RString>,
     /// For runners to differentiate between the matches. Not required.
     pub id: ROption<u64>,
 }
 
+/// The modifier keys held while activating a match
+#[repr(C)]
+#[derive(StableAbi, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub super_key: bool,
+}
+
+/// What activated a match
+#[repr(C)]
+#[derive(StableAbi, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActivationSource {
+    /// `Enter` in the search entry
+    Entry,
+    /// `Enter` on a focused row
+    Row,
+    /// A mouse click on a row
+    Mouse,
+}
+
+/// Describes how a match was activated
+#[repr(C)]
+#[derive(StableAbi, Clone, Copy, Debug)]
+pub struct Activation {
+    pub source: ActivationSource,
+    pub modifiers: Modifiers,
+    /// The mouse button for `ActivationSource::Mouse`: 1 is primary, 2 is middle and 3 is secondary.
+    /// 0 otherwise.
+    pub button: u32,
+}
+
+/// New content of the search entry
+#[repr(C)]
+#[derive(StableAbi, Clone, Debug)]
+pub struct EntryText {
+    pub text: RString,
+    /// The cursor position in characters, the end of the text if not set
+    pub cursor: ROption<u32>,
+}
+
+/// Content of the preview pane for a selected match
+#[repr(C)]
+#[derive(StableAbi, Clone, Debug)]
+pub enum Preview {
+    /// Text with Pango markup
+    Markup(RString),
+    /// A path to an image
+    Image(RString),
+    /// A path to a text file, of which the beginning is shown
+    File(RString),
+}
+
+/// A program for anyrun to launch once its window has closed
+#[repr(C)]
+#[derive(StableAbi, Clone, Debug)]
+pub struct LaunchCommand {
+    /// The program followed by its arguments
+    pub argv: RVec<RString>,
+    pub working_dir: ROption<RString>,
+    /// Variables to add to the environment of the program
+    pub env: RVec<Tuple2<RString, RString>>,
+    /// Whether to run the program in the configured terminal
+    pub terminal: bool,
+}
+
+/// A local file for anyrun to open once its window has closed
+#[repr(C)]
+#[derive(StableAbi, Clone, Debug)]
+pub struct OpenFile {
+    pub path: RString,
+    /// Show the file in its folder instead of opening it
+    pub reveal: bool,
+}
+
+/// One representation of the copied content
+#[repr(C)]
+#[derive(StableAbi, Clone, Debug)]
+pub struct CopyOffer {
+    /// Detected from the content if not set
+    pub mime_type: ROption<RString>,
+    pub bytes: RVec<u8>,
+}
+
+/// Which clipboard to copy to
+#[repr(C)]
+#[derive(StableAbi, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClipboardType {
+    Regular,
+    /// The primary selection, pasted with a middle click
+    Primary,
+    Both,
+}
+
+/// Content for anyrun to copy once its window has closed
+#[repr(C)]
+#[derive(StableAbi, Clone, Debug)]
+pub struct ClipboardContent {
+    /// The same content in different representations, e.g. `text/plain` and `text/html`
+    pub offers: RVec<CopyOffer>,
+    pub clipboard: ClipboardType,
+    /// Stop serving the content after this many pastes, at least one
+    pub paste_limit: ROption<u32>,
+    /// Stop serving the content after this many seconds
+    pub timeout: ROption<u32>,
+}
+
+/// A button on a notification, which launches the command when clicked
+#[repr(C)]
+#[derive(StableAbi, Clone, Debug)]
+pub struct NotificationAction {
+    pub label: RString,
+    pub command: LaunchCommand,
+}
+
+/// A desktop notification for anyrun to send once its window has closed
+#[repr(C)]
+#[derive(StableAbi, Clone, Debug)]
+pub struct Notification {
+    pub summary: RString,
+    pub body: RString,
+    /// An icon name or a path to an image
+    pub icon: ROption<RString>,
+    pub actions: RVec<NotificationAction>,
+    /// Close the notification after this many milliseconds, the server decides if not set
+    pub timeout: ROption<u32>,
+}
+
+/// A question for the user to answer before the plugin acts on a match
+#[repr(C)]
+#[derive(StableAbi, Clone, Debug)]
+pub struct Confirmation {
+    /// The question, e.g. "Shut down the computer?"
+    pub prompt: RString,
+    /// The label of the confirm button, "Confirm" if not set
+    pub confirm_label: ROption<RString>,
+    /// The match to handle again once the user has confirmed
+    pub selection: Match,
+}
+
+/// How the value of a form field is entered
+#[repr(C)]
+#[derive(StableAbi, Clone, Debug)]
+pub enum FormFieldKind {
+    /// Free text, with a placeholder shown while it is empty
+    Text { placeholder: RString },
+    /// One of the choices
+    Choice { choices: RVec<RString> },
+}
+
+/// A field of a form
+#[repr(C)]
+#[derive(StableAbi, Clone, Debug)]
+pub struct FormField {
+    /// Identifies the value of the field once the form is submitted
+    pub name: RString,
+    pub label: RString,
+    pub kind: FormFieldKind,
+    /// The initial text, or the initially selected choice
+    pub default: RString,
+}
+
+/// A form for anyrun to show in place of the matches
+#[repr(C)]
+#[derive(StableAbi, Clone, Debug)]
+pub struct Form {
+    pub title: RString,
+    pub fields: RVec<FormField>,
+    /// The label of the submit button, "Submit" if not set
+    pub submit_label: ROption<RString>,
+    /// The match passed back to the plugin along with the values
+    pub selection: Match,
+}
+
+/// The value of a form field once the form is submitted
+#[repr(C)]
+#[derive(StableAbi, Clone, Debug)]
+pub struct FormValue {
+    pub name: RString,
+    pub value: RString,
+}
+
 /// For determining how anyrun should proceed after the plugin has handled a match selection
 #[repr(C)]
 #[derive(StableAbi)]
@@ -56,8 +253,27 @@ pub enum HandleResult {
     Refresh(bool),
     /// Copy the content, due to how copying works it must be done like this.
     Copy(RVec<u8>),
+    /// Like `Copy`, with control over MIME types, the clipboard and how long the content is served.
+    CopyContent(ClipboardContent),
     /// Output the content to stdout, printing to stdout has issues in plugins.
     Stdout(RVec<u8>),
+    /// Enter a submenu with the inner value as its title. The plugin is set to exclusive mode
+    /// and the entry is cleared, going back restores the previous input and selection.
+    Submenu(RString),
+    /// Replace the text in the entry and move the cursor, which refreshes the items.
+    SetInput(EntryText),
+    /// Close and launch the program detached from anyrun, so plugins don't need to fork themselves.
+    Launch(LaunchCommand),
+    /// Close and open the URI with its default handler through the desktop portal.
+    OpenUri(RString),
+    /// Close and open the file with its default application through the desktop portal.
+    OpenFile(OpenFile),
+    /// Close and send a desktop notification, e.g. to report the result of the selection.
+    Notify(Notification),
+    /// Ask the user to confirm first, the selection in it is only handled again if they do.
+    Confirm(Confirmation),
+    /// Show a form in place of the matches, the values are passed to `handle_form` once submitted.
+    Form(Form),
 }
 
 #[repr(C)]
@@ -14,27 +14,9 @@ pub fn handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let function = parse_macro_input!(item as syn::ItemFn);
     let fn_name = &function.sig.ident;
 
-    let data = if function.sig.inputs.len() == 2 {
-        if match function.sig.inputs.last() {
-            ::core::option::Option::Some(syn::FnArg::Typed(pat)) => match &*pat.ty {
-                Type::Reference(reference) => {
-                    reference.mutability.is_some()
-                }
-                _ => return quote! { compile_error!("Last argument must be either a reference to the shared data or should not be present at all.") }.into(),
-            },
-            ::core::option::Option::Some(_) => return quote! { compile_error!("`self` argument, really?") }.into(),
-            ::core::option::Option::None => unreachable!(),
-        } {
-            quote! {
-                ANYRUN_INTERNAL_DATA.write().unwrap().as_mut().unwrap(),
-            }
-        } else {
-            quote! {
-                ANYRUN_INTERNAL_DATA.read().unwrap().as_ref().unwrap(),
-            }
-        }
-    } else {
-        quote! {}
+    let data = match data_argument(&function, 1) {
+        Ok(data) => data,
+        Err(error) => return error,
     };
 
     quote! {
@@ -53,6 +35,212 @@ pub fn handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
     .into()
 }
 
+/// Like `handler`, but also receives an `Activation` describing how the match was activated (keyboard or mouse,
+/// held modifiers and the mouse button) as its second argument. The third argument can be one of:
+/// - &T
+/// - &mut T
+/// - <Nothing>
+/// where T is the type returned by `init`.
+///
+/// Should return a `HandleResult` with the appropriate action. This function is optional, `handler` is used
+/// for all activations otherwise.
+#[proc_macro_attribute]
+pub fn activation_handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as syn::ItemFn);
+    let fn_name = &function.sig.ident;
+
+    let data = match data_argument(&function, 2) {
+        Ok(data) => data,
+        Err(error) => return error,
+    };
+
+    quote! {
+        #[::abi_stable::sabi_extern_fn]
+        fn anyrun_internal_handle_activation(
+            selection: ::anyrun_plugin::anyrun_interface::Match,
+            activation: ::anyrun_plugin::anyrun_interface::Activation,
+        ) -> ::anyrun_plugin::anyrun_interface::HandleResult {
+            #function
+
+            #fn_name(
+                selection,
+                activation,
+                #data
+            )
+        }
+    }
+    .into()
+}
+
+/// The function to handle a submitted form. Takes the `Match` of the form as its first argument and the `FormValue`s of
+/// its fields as the second, and the third argument can be one of:
+/// - &T
+/// - &mut T
+/// - <Nothing>
+/// where T is the type returned by `init`.
+///
+/// Should return a `HandleResult` with the appropriate action. This function is optional, but needed by plugins
+/// returning `HandleResult::Form`.
+#[proc_macro_attribute]
+pub fn form_handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as syn::ItemFn);
+    let fn_name = &function.sig.ident;
+
+    let data = match data_argument(&function, 2) {
+        Ok(data) => data,
+        Err(error) => return error,
+    };
+
+    quote! {
+        #[::abi_stable::sabi_extern_fn]
+        fn anyrun_internal_handle_form(
+            selection: ::anyrun_plugin::anyrun_interface::Match,
+            values: ::abi_stable::std_types::RVec<::anyrun_plugin::anyrun_interface::FormValue>,
+        ) -> ::anyrun_plugin::anyrun_interface::HandleResult {
+            #function
+
+            #fn_name(
+                selection,
+                values,
+                #data
+            )
+        }
+    }
+    .into()
+}
+
+/// The function to complete the entry text on `Tab`. Takes the selected `Match` as its first argument, and the second argument can be one of:
+/// - &T
+/// - &mut T
+/// - <Nothing>
+/// where T is the type returned by `init`.
+///
+/// Should return an `ROption<EntryText>` with the new entry text, or `RNone` to let `Tab` through.
+/// This function is optional.
+#[proc_macro_attribute]
+pub fn complete(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as syn::ItemFn);
+    let fn_name = &function.sig.ident;
+
+    let data = match data_argument(&function, 1) {
+        Ok(data) => data,
+        Err(error) => return error,
+    };
+
+    quote! {
+        #[::abi_stable::sabi_extern_fn]
+        fn anyrun_internal_complete(
+            selection: ::anyrun_plugin::anyrun_interface::Match,
+        ) -> ::abi_stable::std_types::ROption<::anyrun_plugin::anyrun_interface::EntryText> {
+            #function
+
+            #fn_name(
+                selection,
+                #data
+            )
+        }
+    }
+    .into()
+}
+
+/// The function to provide the preview of the selected match. Takes the `Match` as its first argument, and the second
+/// argument can be one of:
+/// - &T
+/// - &mut T
+/// - <Nothing>
+/// where T is the type returned by `init`.
+///
+/// Should return an `ROption<Preview>` with the content of the preview pane, or `RNone` to hide it. This function is
+/// optional, and called on the main thread, so it should return quickly.
+#[proc_macro_attribute]
+pub fn preview(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as syn::ItemFn);
+    let fn_name = &function.sig.ident;
+
+    let data = match data_argument(&function, 1) {
+        Ok(data) => data,
+        Err(error) => return error,
+    };
+
+    quote! {
+        #[::abi_stable::sabi_extern_fn]
+        fn anyrun_internal_preview(
+            selection: ::anyrun_plugin::anyrun_interface::Match,
+        ) -> ::abi_stable::std_types::ROption<::anyrun_plugin::anyrun_interface::Preview> {
+            #function
+
+            #fn_name(
+                selection,
+                #data
+            )
+        }
+    }
+    .into()
+}
+
+/// The function to provide extra CSS classes for the row of a match, for users to style it by. Takes the `Match` as
+/// its first argument, and the second argument can be one of:
+/// - &T
+/// - &mut T
+/// - <Nothing>
+/// where T is the type returned by `init`.
+///
+/// Should return an `RVec<RString>` of class names, like `warning` or `urgent`. This function is optional.
+#[proc_macro_attribute]
+pub fn classes(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as syn::ItemFn);
+    let fn_name = &function.sig.ident;
+
+    let data = match data_argument(&function, 1) {
+        Ok(data) => data,
+        Err(error) => return error,
+    };
+
+    quote! {
+        #[::abi_stable::sabi_extern_fn]
+        fn anyrun_internal_classes(
+            selection: ::anyrun_plugin::anyrun_interface::Match,
+        ) -> ::abi_stable::std_types::RVec<::abi_stable::std_types::RString> {
+            #function
+
+            #fn_name(
+                selection,
+                #data
+            )
+        }
+    }
+    .into()
+}
+
+/// Function that is called when anyrun goes back from a submenu entered with `HandleResult::Submenu`,
+/// so the plugin can return to its previous state. Its argument can be one of:
+/// - &T
+/// - &mut T
+/// - <Nothing>
+/// where T is the type returned by `init`.
+///
+/// This function is optional.
+#[proc_macro_attribute]
+pub fn navigate_back(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as syn::ItemFn);
+    let fn_name = &function.sig.ident;
+
+    let data = match data_argument(&function, 0) {
+        Ok(data) => data,
+        Err(error) => return error,
+    };
+
+    quote! {
+        #[::abi_stable::sabi_extern_fn]
+        fn anyrun_internal_navigate_back() {
+            #function
+
+            #fn_name(#data)
+        }
+    }
+    .into()
+}
+
 /// Function that takes the current text input as an `RString` as the first argument, and the second argument can be one of:
 /// - &T
 /// - &mut T
@@ -65,36 +253,19 @@ pub fn get_matches(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let function = parse_macro_input!(item as syn::ItemFn);
     let fn_name = &function.sig.ident;
 
-    let fn_call = if function.sig.inputs.len() == 2 {
-        let data = if match function.sig.inputs.last() {
-            ::core::option::Option::Some(syn::FnArg::Typed(pat)) => match &*pat.ty {
-                Type::Reference(reference) => {
-                    reference.mutability.is_some()
-                }
-                _ => return quote! { compile_error!("Last argument must be either a reference to the shared data or should not be present at all.") }.into(),
-            },
-            ::core::option::Option::Some(_) => return quote! { compile_error!("`self` argument, really?") }.into(),
-            ::core::option::Option::None => unreachable!(),
-        } {
-            quote! {
-                ANYRUN_INTERNAL_DATA.write().unwrap().as_mut()
-            }
-        } else {
-            quote! {
-                ANYRUN_INTERNAL_DATA.read().unwrap().as_ref()
-            }
-        };
-        quote! {
+    // Matches are asked for before `init` may have returned the data, so there are none then
+    let fn_call = match data_option(&function, 1) {
+        Ok(Some(data)) => quote! {
             if let ::core::option::Option::Some(data) = #data {
                 #fn_name(input, data)
             } else {
                 ::abi_stable::std_types::RVec::new()
             }
-        }
-    } else {
-        quote! {
+        },
+        Ok(None) => quote! {
             #fn_name(input)
-        }
+        },
+        Err(error) => return error,
     };
 
     quote! {
@@ -161,6 +332,52 @@ pub fn init(_attr: TokenStream, item: TokenStream) -> TokenStream {
         static ANYRUN_INTERNAL_DATA: ::std::sync::RwLock<Option<#data_type>> =
             ::std::sync::RwLock::new(None);
 
+        // Items defined by the plugin itself shadow these glob imported fallbacks
+        #[allow(unused_imports)]
+        use self::anyrun_internal_defaults::*;
+
+        mod anyrun_internal_defaults {
+            #[::abi_stable::sabi_extern_fn]
+            pub fn anyrun_internal_navigate_back() {}
+
+            #[::abi_stable::sabi_extern_fn]
+            pub fn anyrun_internal_complete(
+                _selection: ::anyrun_plugin::anyrun_interface::Match,
+            ) -> ::abi_stable::std_types::ROption<::anyrun_plugin::anyrun_interface::EntryText> {
+                ::abi_stable::std_types::ROption::RNone
+            }
+
+            #[::abi_stable::sabi_extern_fn]
+            pub fn anyrun_internal_handle_activation(
+                selection: ::anyrun_plugin::anyrun_interface::Match,
+                _activation: ::anyrun_plugin::anyrun_interface::Activation,
+            ) -> ::anyrun_plugin::anyrun_interface::HandleResult {
+                super::anyrun_internal_handle_selection(selection)
+            }
+
+            #[::abi_stable::sabi_extern_fn]
+            pub fn anyrun_internal_handle_form(
+                _selection: ::anyrun_plugin::anyrun_interface::Match,
+                _values: ::abi_stable::std_types::RVec<::anyrun_plugin::anyrun_interface::FormValue>,
+            ) -> ::anyrun_plugin::anyrun_interface::HandleResult {
+                ::anyrun_plugin::anyrun_interface::HandleResult::Close
+            }
+
+            #[::abi_stable::sabi_extern_fn]
+            pub fn anyrun_internal_preview(
+                _selection: ::anyrun_plugin::anyrun_interface::Match,
+            ) -> ::abi_stable::std_types::ROption<::anyrun_plugin::anyrun_interface::Preview> {
+                ::abi_stable::std_types::ROption::RNone
+            }
+
+            #[::abi_stable::sabi_extern_fn]
+            pub fn anyrun_internal_classes(
+                _selection: ::anyrun_plugin::anyrun_interface::Match,
+            ) -> ::abi_stable::std_types::RVec<::abi_stable::std_types::RString> {
+                ::abi_stable::std_types::RVec::new()
+            }
+        }
+
         #[::abi_stable::export_root_module]
         fn anyrun_internal_init_root_module() -> ::anyrun_plugin::anyrun_interface::PluginRef {
             use ::abi_stable::prefix_type::PrefixTypeTrait;
@@ -170,6 +387,12 @@ pub fn init(_attr: TokenStream, item: TokenStream) -> TokenStream {
                 get_matches: anyrun_internal_get_matches,
                 poll_matches: anyrun_internal_poll_matches,
                 handle_selection: anyrun_internal_handle_selection,
+                navigate_back: anyrun_internal_navigate_back,
+                complete: anyrun_internal_complete,
+                handle_activation: anyrun_internal_handle_activation,
+                handle_form: anyrun_internal_handle_form,
+                preview: anyrun_internal_preview,
+                classes: anyrun_internal_classes,
             }
             .leak_into_prefix()
         }
@@ -250,3 +473,27 @@ pub fn config_args(_attr: TokenStream, item: TokenStream) -> TokenStream {
     }
     .into()
 }
+
+/// The data returned by `init` to pass to `function` as its argument at `index`, borrowed mutably
+/// or not depending on the reference the argument is. `None` if `function` takes no such argument.
+fn data_argument(function: &syn::ItemFn, index: usize) -> Result<Option<syn::Expr>, TokenStream> {
+    Ok(data_option(function, index)?.map(|data| parse_quote! { #data.unwrap() }))
+}
+
+/// Like [`data_argument`], but as an `Option` of the data, which is `None` until `init` has
+/// returned it.
+fn data_option(function: &syn::ItemFn, index: usize) -> Result<Option<syn::Expr>, TokenStream> {
+    match function.sig.inputs.iter().nth(index) {
+        ::core::option::Option::Some(syn::FnArg::Typed(pat)) => match &*pat.ty {
+            Type::Reference(reference) if reference.mutability.is_some() => Ok(Some(parse_quote! {
+                ANYRUN_INTERNAL_DATA.write().unwrap().as_mut()
+            })),
+            Type::Reference(_) => Ok(Some(parse_quote! {
+                ANYRUN_INTERNAL_DATA.read().unwrap().as_ref()
+            })),
+            _ => Err(quote! { compile_error!("Last argument must be either a reference to the shared data or should not be present at all.") }.into()),
+        },
+        ::core::option::Option::Some(_) => Err(quote! { compile_error!("`self` argument, really?") }.into()),
+        ::core::option::Option::None => Ok(None),
+    }
+}
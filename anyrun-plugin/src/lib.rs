@@ -3,10 +3,24 @@ The crate for building plugins for Anyrun.
 
 Each plugin needs 4 functions defined, `init`, `info`, `get_matches` and the `handler`. Documentation
 on what each of these should be is found in their respective attribute macros.
+
+Optional functions, like `activation_handler`, `form_handler`, `navigate_back`, `complete`,
+`preview` and `classes`, can be defined the same way and fall back to a default behaviour otherwise.
+
+The `markup` module has helpers for building Pango markup for matches with `use_pango` set.
 !*/
 
-pub use anyrun_interface::{self, HandleResult, Match, PluginInfo};
-pub use anyrun_macros::{get_matches, handler, info, init};
+pub mod markup;
+
+pub use anyrun_interface::{
+    self, Activation, ActivationSource, ClipboardContent, ClipboardType, Confirmation, CopyOffer,
+    EntryText, Form, FormField, FormFieldKind, FormValue, HandleResult, LaunchCommand, Match,
+    Modifiers, Notification, NotificationAction, OpenFile, PluginInfo, Preview,
+};
+pub use anyrun_macros::{
+    activation_handler, classes, complete, form_handler, get_matches, handler, info, init,
+    navigate_back, preview,
+};
 
 /*
 The macro to create a plugin, handles asynchronous execution of getting the matches and the boilerplate
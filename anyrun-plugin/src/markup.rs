@@ -0,0 +1,90 @@
+//! Helpers for building Pango markup for matches with `use_pango` set, so text from files or the
+//! web can't break it.
+
+use std::fmt;
+
+use abi_stable::std_types::RString;
+
+/// Escapes the characters that have a meaning in Pango markup
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\'' => escaped.push_str("&#39;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Pango markup that is always valid, as any text in it is escaped
+///
+/// ```
+/// use anyrun_plugin::markup::Markup;
+///
+/// let title = Markup::text("Fish & Chips")
+///     .bold()
+///     .append(Markup::text(" <3").color("#e01b24"));
+/// assert_eq!(
+///     title.as_str(),
+///     r##"<b>Fish &amp; Chips</b><span foreground="#e01b24"> &lt;3</span>"##
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Markup(String);
+
+impl Markup {
+    /// Plain text, escaped
+    pub fn text(text: &str) -> Self {
+        Self(escape(text))
+    }
+
+    pub fn bold(self) -> Self {
+        Self(format!("<b>{}</b>", self.0))
+    }
+
+    pub fn italic(self) -> Self {
+        Self(format!("<i>{}</i>", self.0))
+    }
+
+    /// Sets the text color, as a name like `red` or a hex value like `#ff0000`
+    pub fn color(self, color: &str) -> Self {
+        Self(format!(
+            r#"<span foreground="{}">{}</span>"#,
+            escape(color),
+            self.0
+        ))
+    }
+
+    /// Appends the other markup after this one
+    pub fn append(mut self, other: Markup) -> Self {
+        self.0.push_str(&other.0);
+        self
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Markup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<Markup> for String {
+    fn from(markup: Markup) -> Self {
+        markup.0
+    }
+}
+
+impl From<Markup> for RString {
+    fn from(markup: Markup) -> Self {
+        markup.0.into()
+    }
+}
@@ -1,12 +1,16 @@
-use anyrun_interface::PluginRef as Plugin;
-use clap::{Parser, ValueEnum};
+use anyrun_interface::{Activation, ClipboardType, FormField, Match, PluginRef as Plugin};
+use clap::{Parser, Subcommand, ValueEnum};
 use gtk::{gdk::Rectangle, gio, glib};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{
     fs,
     path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
 };
 
+use crate::status::StatusBar;
+
 // Config struct and its implementation
 #[anyrun_macros::config_args]
 #[derive(Deserialize)]
@@ -17,15 +21,26 @@ pub struct Config {
     #[serde(default = "Config::default_height")]
     pub height: RelativeNum,
 
+    #[serde(default)]
+    pub max_height: Option<RelativeNum>,
+
     #[serde(default = "Config::default_edges")]
     pub edges: Vec<Edge>,
 
     #[serde(default)]
     pub margin: Vec<RelativeNum>,
 
+    #[serde(default)]
+    pub position: Position,
+
     #[serde(default = "Config::default_plugins")]
     pub plugins: Vec<PathBuf>,
 
+    #[serde(default = "Config::default_icon_size")]
+    pub plugin_icon_size: i32,
+    #[serde(default = "Config::default_icon_size")]
+    pub match_icon_size: i32,
+
     #[serde(default)]
     pub hide_match_icons: bool,
     #[serde(default)]
@@ -35,20 +50,52 @@ pub struct Config {
     #[serde(default)]
     pub steal_focus: bool,
     #[serde(default)]
+    pub close_on_click_outside: bool,
+    #[serde(default)]
+    pub close_on_focus_loss: bool,
+    #[serde(default)]
     pub ignore_exclusive_zones: bool,
     #[serde(default)]
     pub show_results_immediately: bool,
+    #[serde(default)]
+    pub show_status: bool,
 
     #[serde(default)]
     pub save_entry_state: bool,
 
+    #[serde(default)]
+    pub window_type: WindowType,
     #[serde(default)]
     pub layer: Layer,
     #[serde(default)]
     pub bottom_entry: bool,
+    #[serde(default)]
+    pub preview: PreviewPosition,
+    #[serde(default)]
+    pub layout: Layout,
+    #[serde(default)]
+    pub theme: Theme,
+    #[serde(default)]
+    pub grid_plugins: Vec<PathBuf>,
+
+    #[serde(default)]
+    pub terminal: Vec<String>,
+    #[serde(default)]
+    pub launch_with_systemd_run: bool,
 }
 
 impl Config {
+    /// Whether the preview pane is shown, which it never is in the bar layout
+    pub fn shows_preview(&self) -> bool {
+        self.preview != PreviewPosition::Hidden && self.layout != Layout::Bar
+    }
+
+    /// Whether the window covers the monitor with the content placed inside it, as the position
+    /// of the pointer and clicks are only known inside the window
+    pub fn covers_monitor(&self) -> bool {
+        matches!(self.position, Position::Pointer) || self.close_on_click_outside
+    }
+
     fn default_width() -> RelativeNum {
         RelativeNum::Fraction(0.5)
     }
@@ -57,6 +104,10 @@ impl Config {
         RelativeNum::Absolute(0)
     }
 
+    fn default_icon_size() -> i32 {
+        32
+    }
+
     fn default_edges() -> Vec<Edge> {
         vec![Edge::Top]
     }
@@ -76,23 +127,37 @@ impl Default for Config {
         Self {
             width: Self::default_width(),
             height: Self::default_height(),
+            max_height: None,
             edges: Self::default_edges(),
             margin: Vec::default(),
+            position: Position::default(),
             plugins: Self::default_plugins(),
+            plugin_icon_size: Self::default_icon_size(),
+            match_icon_size: Self::default_icon_size(),
             hide_match_icons: false,
             hide_plugins_icons: true,
             hide_plugin_info: false,
             ignore_exclusive_zones: false,
             steal_focus: false,
+            close_on_click_outside: false,
+            close_on_focus_loss: false,
             show_results_immediately: false,
+            show_status: false,
+            window_type: WindowType::default(),
             layer: Layer::default(),
             bottom_entry: false,
+            preview: PreviewPosition::default(),
+            layout: Layout::default(),
+            theme: Theme::default(),
+            grid_plugins: Vec::new(),
             save_entry_state: false,
+            terminal: Vec::new(),
+            launch_with_systemd_run: false,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, Deserialize, ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
 pub enum Edge {
     Left,
     Right,
@@ -137,8 +202,78 @@ impl Default for Layer {
     }
 }
 
+// Enum for what kind of window to open
+#[derive(Deserialize, Clone, Copy, ValueEnum)]
+pub enum WindowType {
+    /// A layer-shell surface if the compositor supports it, a regular window otherwise
+    Auto,
+    LayerShell,
+    Toplevel,
+}
+
+impl Default for WindowType {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+impl WindowType {
+    pub fn uses_layer_shell(self) -> bool {
+        match self {
+            WindowType::Auto => gtk_layer_shell::is_supported(),
+            WindowType::LayerShell => true,
+            WindowType::Toplevel => false,
+        }
+    }
+}
+
+// Enum for where to show the preview pane
+#[derive(Deserialize, Clone, Copy, PartialEq, ValueEnum)]
+pub enum PreviewPosition {
+    Right,
+    Bottom,
+    Hidden,
+}
+
+impl Default for PreviewPosition {
+    fn default() -> Self {
+        Self::Right
+    }
+}
+
+// Enum for how the matches are laid out
+#[derive(Deserialize, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Layout {
+    List,
+    Grid,
+    Bar,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::List
+    }
+}
+
+// Enum for the built-in stylesheet to use below the user's `style.css`
+#[derive(Deserialize, Clone, Copy, ValueEnum)]
+pub enum Theme {
+    /// Only the GTK theme
+    Gtk,
+    Light,
+    Dark,
+    /// Light or dark following the color scheme preference of the desktop
+    Auto,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::Gtk
+    }
+}
+
 // RelativeNum enum and its implementation
-#[derive(Deserialize, Clone, Copy)]
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
 pub enum RelativeNum {
     Absolute(i32),
     Fraction(f32),
@@ -179,34 +314,220 @@ pub struct Args {
     pub config_dir: Option<String>,
     #[command(flatten)]
     pub config: ConfigArgs,
+    #[command(subcommand)]
+    pub command: Option<InternalCommand>,
 }
 
-// Enum for positions
-#[derive(Deserialize, Clone, Copy, ValueEnum)]
-enum Position {
-    Top,
+// Enum for the commands anyrun runs itself with, not meant to be used directly
+#[derive(Subcommand)]
+pub enum InternalCommand {
+    /// Send the notification read from stdin and wait for one of its actions
+    #[command(hide = true)]
+    WaitForNotification,
+}
+
+// Enum for where to place the window on the monitor it's shown on
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(try_from = "PositionConfig")]
+pub enum Position {
+    /// Anchored to `edges` with `margin`
+    Edges,
+    /// In the middle
+    Center,
+    /// Centered horizontally, with the given fraction of the height above it
+    Top(f32),
+    /// At the mouse pointer
+    Pointer,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self::Edges
+    }
+}
+
+impl Position {
+    /// `Top` with the fraction, which has to be from 0 to 1
+    fn top(fraction: f32) -> Result<Self, String> {
+        if (0.0..=1.0).contains(&fraction) {
+            Ok(Self::Top(fraction))
+        } else {
+            Err(format!(
+                "Invalid fraction {} for the top position, expected one from 0 to 1",
+                fraction
+            ))
+        }
+    }
+}
+
+// `Position` as written in the config, checked when it's converted
+#[derive(Deserialize)]
+enum PositionConfig {
+    Edges,
     Center,
+    Top(f32),
+    Pointer,
+}
+
+impl TryFrom<PositionConfig> for Position {
+    type Error = String;
+
+    fn try_from(value: PositionConfig) -> Result<Self, Self::Error> {
+        match value {
+            PositionConfig::Edges => Ok(Self::Edges),
+            PositionConfig::Center => Ok(Self::Center),
+            PositionConfig::Top(fraction) => Self::top(fraction),
+            PositionConfig::Pointer => Ok(Self::Pointer),
+        }
+    }
+}
+
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (ty, val) = value.split_once(':').unwrap_or((value, "0"));
+
+        match ty {
+            "edges" => Ok(Self::Edges),
+            "center" => Ok(Self::Center),
+            "top" => val
+                .parse()
+                .map_err(|why| format!("Invalid fraction {:?}: {}", val, why))
+                .and_then(Self::top),
+            "pointer" => Ok(Self::Pointer),
+            _ => Err(format!(
+                "Invalid position {:?}, expected edges, center, top[:fraction] or pointer",
+                ty
+            )),
+        }
+    }
+}
+
+// Struct for a program to launch after GTK has finished
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Launch {
+    pub argv: Vec<String>,
+    pub working_dir: Option<PathBuf>,
+    pub env: Vec<(String, String)>,
+    pub terminal: bool,
+    /// The xdg-activation token for the program to take focus with
+    pub activation_token: Option<String>,
+}
+
+// Enum for what to open after GTK has finished
+pub enum OpenTarget {
+    Uri(String),
+    File(PathBuf),
+    /// Show the file in its folder
+    Reveal(PathBuf),
+}
+
+// Struct for something to open with its default handler after GTK has finished
+pub struct Open {
+    pub target: OpenTarget,
+    pub activation_token: Option<String>,
+}
+
+// Struct for content to serve on the clipboard after GTK has finished
+pub struct ClipboardCopy {
+    /// Pairs of MIME type, autodetected if not set, and content
+    pub offers: Vec<(Option<String>, Vec<u8>)>,
+    pub clipboard: ClipboardType,
+    pub paste_limit: Option<usize>,
+    pub timeout: Option<Duration>,
+}
+
+// Struct for a desktop notification to send after GTK has finished
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Notify {
+    pub summary: String,
+    pub body: String,
+    pub icon: Option<String>,
+    /// Pairs of button label and the program to launch when it is clicked
+    pub actions: Vec<(String, Launch)>,
+    pub timeout: Option<i32>,
 }
 
 // Enum for actions after GTK has finished
 pub enum PostRunAction {
-    Copy(Vec<u8>),
+    Copy(ClipboardCopy),
+    Launch(Launch),
+    Open(Open),
+    Notify(Notify),
     None,
 }
 
+// Struct for a match on its way to be handled by its plugin
+pub struct Selection {
+    pub row_id: usize,
+    pub rmatch: Match,
+    /// The index of the plugin in `plugins`
+    pub plugin_id: usize,
+    pub plugin: Plugin,
+    pub activation: Activation,
+}
+
+// Struct for a selection waiting for the user to confirm it
+pub struct PendingConfirmation {
+    pub prompt: String,
+    pub confirm_label: Option<String>,
+    pub selection: Selection,
+}
+
+// Struct for a form waiting for the user to fill it in
+pub struct PendingForm {
+    pub title: String,
+    pub fields: Vec<FormField>,
+    pub submit_label: Option<String>,
+    pub selection: Selection,
+}
+
+// Struct for a navigation level to go back to from a submenu
+pub struct NavLevel {
+    /// The title of the submenu entered from this level
+    pub title: String,
+    pub exclusive: Option<usize>,
+    pub input: String,
+    pub selection: Option<u32>,
+}
+
 // Struct for runtime data
 pub struct RuntimeData {
-    pub exclusive: Option<Plugin>,
+    /// The index of the plugin in `plugins` the matches are limited to
+    pub exclusive: Option<usize>,
+    /// The Pango attributes for the highlights in the titles of matches, from the current CSS
+    pub highlight_attributes: String,
+    pub nav_stack: Vec<NavLevel>,
+    /// Row to select once enough matches have arrived, instead of the first one
+    pub pending_selection: Option<u32>,
+    pub confirmation: Option<PendingConfirmation>,
+    pub form: Option<PendingForm>,
     pub plugins: Vec<Plugin>,
     pub post_run_action: PostRunAction,
     pub config: Config,
     pub error_label: String,
     pub config_dir: PathBuf,
+    /// The user's GtkBuilder template for the list items of matches
+    pub match_template: Option<glib::Bytes>,
     pub geometry: Rectangle,
+    /// Whether the window is a layer-shell surface, or a regular window
+    pub layer_shell: bool,
+    /// The footer with the progress of the plugins, with `show_status` set
+    pub status_bar: Option<StatusBar>,
     pub list_store: gio::ListStore,
     pub app_state: gio::Settings,
 }
 
+impl RuntimeData {
+    /// The plugin the matches are limited to
+    pub fn exclusive_plugin(&self) -> Option<Plugin> {
+        self.exclusive
+            .and_then(|plugin_id| self.plugins.get(plugin_id))
+            .copied()
+    }
+}
+
 /// The naming scheme for CSS styling
 ///
 /// Refer to [GTK 3.0 CSS Overview](https://docs.gtk.org/gtk3/css-overview.html)
@@ -218,6 +539,15 @@ pub mod style_names {
     pub const MATCH: &str = "match";
     pub const MATCH_TITLE: &str = "match-title";
     pub const MATCH_DESC: &str = "match-desc";
+    pub const MATCH_HIGHLIGHT: &str = "match-highlight";
+    pub const EXCLUSIVE: &str = "exclusive";
+    pub const BREADCRUMB: &str = "breadcrumb";
+    pub const CONFIRM: &str = "confirm";
+    pub const FORM: &str = "form";
+    pub const PREVIEW: &str = "preview";
+    pub const GRID: &str = "grid";
+    pub const BAR: &str = "bar";
+    pub const STATUS: &str = "status";
 }
 
 pub const APP_ID: &str = "com.kirottu.anyrun";
@@ -280,3 +610,41 @@ pub fn determine_config_dir(config_dir_arg: &Option<String>) -> PathBuf {
     }
     default_config_dir()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_numbers_are_resolved_against_the_monitor() {
+        assert_eq!(RelativeNum::Absolute(300).to_val(1920), 300);
+        assert_eq!(RelativeNum::Fraction(0.5).to_val(1920), 960);
+        // Rounded down to whole pixels
+        assert_eq!(RelativeNum::Fraction(0.3).to_val(1081), 324);
+        assert_eq!(RelativeNum::from("fraction:0.25").to_val(1080), 270);
+        assert_eq!(RelativeNum::from("absolute:42").to_val(1080), 42);
+    }
+
+    #[test]
+    fn positions_are_parsed() {
+        assert_eq!("edges".parse(), Ok(Position::Edges));
+        assert_eq!("center".parse(), Ok(Position::Center));
+        assert_eq!("pointer".parse(), Ok(Position::Pointer));
+        assert_eq!("top".parse(), Ok(Position::Top(0.0)));
+        assert_eq!("top:0.3".parse(), Ok(Position::Top(0.3)));
+
+        assert!("top:high".parse::<Position>().is_err());
+        assert!("top:1.5".parse::<Position>().is_err());
+        assert!("top:-0.1".parse::<Position>().is_err());
+        assert!("bottom".parse::<Position>().is_err());
+    }
+
+    #[test]
+    fn top_fractions_in_the_config_are_checked() {
+        assert_eq!(
+            ron::from_str::<Position>("Top(0.3)").ok(),
+            Some(Position::Top(0.3))
+        );
+        assert!(ron::from_str::<Position>("Top(2.0)").is_err());
+    }
+}
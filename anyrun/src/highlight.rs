@@ -0,0 +1,177 @@
+use std::ops::Range;
+
+use gtk::{
+    glib::{self, translate::IntoGlib},
+    pango,
+    prelude::*,
+};
+
+use crate::config::style_names;
+
+/// The look of highlights unless the user's CSS says otherwise
+pub const DEFAULT_CSS: &str = "label#match-highlight { font-weight: bold; }";
+
+/// Finds the byte ranges of `text` matched by `query`, ignoring case. Each word of the query is
+/// looked for as a whole first, if one of them is missing all the characters of the query are
+/// looked for in order. Returns no ranges if neither matches.
+pub fn match_ranges(text: &str, query: &str) -> Vec<Range<usize>> {
+    let chars: Vec<(usize, char)> = text
+        .char_indices()
+        .map(|(index, c)| (index, fold_case(c)))
+        .collect();
+    let end_of = |pos: usize| chars.get(pos).map_or(text.len(), |(index, _)| *index);
+
+    let mut ranges = Vec::new();
+    for word in query.split_whitespace() {
+        let word: Vec<char> = word.chars().map(fold_case).collect();
+        let pos = chars
+            .windows(word.len())
+            .position(|window| window.iter().map(|(_, c)| *c).eq(word.iter().copied()));
+
+        match pos {
+            Some(pos) => ranges.push(chars[pos].0..end_of(pos + word.len())),
+            None => return subsequence_ranges(&chars, query, end_of),
+        }
+    }
+
+    ranges.sort_by_key(|range| range.start);
+    merge_ranges(ranges)
+}
+
+fn subsequence_ranges(
+    chars: &[(usize, char)],
+    query: &str,
+    end_of: impl Fn(usize) -> usize,
+) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut pos = 0;
+
+    for query_char in query.chars().filter(|c| !c.is_whitespace()).map(fold_case) {
+        let Some(offset) = chars[pos..].iter().position(|(_, c)| *c == query_char) else {
+            return Vec::new();
+        };
+        pos += offset;
+        ranges.push(chars[pos].0..end_of(pos + 1));
+        pos += 1;
+    }
+
+    merge_ranges(ranges)
+}
+
+/// Joins overlapping and adjacent ranges, which have to be sorted by their start
+fn merge_ranges(ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Escapes `text` for Pango markup, with the ranges wrapped in spans with the attributes, which
+/// each start with a space
+pub fn highlight_markup(text: &str, ranges: &[Range<usize>], attributes: &str) -> String {
+    let mut markup = String::new();
+    let mut last = 0;
+
+    for range in ranges {
+        markup.push_str(&glib::markup_escape_text(&text[last..range.start]));
+        markup.push_str(&format!(
+            "<span{}>{}</span>",
+            attributes,
+            glib::markup_escape_text(&text[range.clone()])
+        ));
+        last = range.end;
+    }
+    markup.push_str(&glib::markup_escape_text(&text[last..]));
+
+    markup
+}
+
+/// The Pango span attributes for highlights, taken from the `color`, `font-weight` and
+/// `font-style` the CSS gives the `match-highlight` name. The color is only set if the CSS gives
+/// highlights one of their own, so that they keep the color of their row otherwise, like the one
+/// of the selected row.
+pub fn highlight_attributes() -> String {
+    let label = gtk::Label::builder()
+        .name(style_names::MATCH_HIGHLIGHT)
+        .build();
+
+    let mut attributes = String::new();
+
+    let color = label.style_context().color();
+    if color != gtk::Label::new(None).style_context().color() {
+        attributes.push_str(&format!(
+            r##" foreground="#{:02x}{:02x}{:02x}""##,
+            (color.red() * 255.0).round() as u8,
+            (color.green() * 255.0).round() as u8,
+            (color.blue() * 255.0).round() as u8,
+        ));
+    }
+
+    if let Some(font) = label.pango_context().font_description() {
+        attributes.push_str(&format!(r#" weight="{}""#, font.weight().into_glib()));
+        attributes.push_str(match font.style() {
+            pango::Style::Italic => r#" style="italic""#,
+            pango::Style::Oblique => r#" style="oblique""#,
+            _ => "",
+        });
+    }
+
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_are_matched_ignoring_case() {
+        assert_eq!(
+            match_ranges("Firefox Web Browser", "web FIRE"),
+            vec![0..4, 8..11]
+        );
+    }
+
+    #[test]
+    fn characters_are_matched_in_order_without_a_word() {
+        assert_eq!(match_ranges("Firefox", "ffx"), vec![0..1, 4..5, 6..7]);
+        assert_eq!(match_ranges("Firefox", "xf"), Vec::<Range<usize>>::new());
+        assert_eq!(match_ranges("Firefox", ""), Vec::<Range<usize>>::new());
+    }
+
+    #[test]
+    fn overlapping_and_adjacent_ranges_are_merged() {
+        assert_eq!(match_ranges("Firefox", "fire ref"), vec![0..5]);
+        assert_eq!(match_ranges("Firefox", "fi re"), vec![0..4]);
+    }
+
+    #[test]
+    fn ranges_are_in_bytes_of_multibyte_text() {
+        let text = "Größe ändern";
+        let ranges = match_ranges(text, "ÄND");
+        assert_eq!(ranges, vec![8..12]);
+        assert_eq!(&text[ranges[0].clone()], "änd");
+
+        assert_eq!(
+            match_ranges("日本語のテキスト", "テキ 本"),
+            vec![3..6, 12..18]
+        );
+        // The lowercase of `İ` is longer than it, the range still covers it as it is
+        assert_eq!(match_ranges("İstanbul", "ist"), vec![0..4]);
+    }
+
+    #[test]
+    fn markup_is_escaped_around_highlights() {
+        assert_eq!(
+            highlight_markup("a<b & c", &[2..3], r#" weight="700""#),
+            r#"a&lt;<span weight="700">b</span> &amp; c"#
+        );
+    }
+}
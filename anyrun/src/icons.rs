@@ -0,0 +1,388 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    path::{Path, PathBuf},
+};
+
+use gtk::{gdk, gdk_pixbuf::Pixbuf, gio, glib, prelude::*};
+use log::*;
+
+use crate::config::style_names;
+
+/// Shown when none of the icons asked for can be found
+const GENERIC_ICON: &str = "application-x-executable";
+/// Shown while an image is being loaded, if the icon theme has it
+const LOADING_ICON: &str = "image-loading-symbolic";
+/// How many loaded images are kept for the rows built later, also for other inputs
+const TEXTURE_CACHE_SIZE: usize = 256;
+/// The sizes of the thumbnails in the XDG thumbnail cache, with the directories they are in
+const THUMBNAIL_SIZES: [(i32, &str); 4] = [
+    (128, "normal"),
+    (256, "large"),
+    (512, "x-large"),
+    (1024, "xx-large"),
+];
+
+thread_local! {
+    static TEXTURE_CACHE: RefCell<TextureCache> = RefCell::new(TextureCache::default());
+}
+
+/// The most recently used textures, by the icon and the size they were loaded for
+#[derive(Default)]
+struct TextureCache {
+    entries: VecDeque<((String, i32), gdk::Texture)>,
+}
+
+impl TextureCache {
+    fn get(&mut self, icon: &str, pixel_size: i32) -> Option<gdk::Texture> {
+        let index = self
+            .entries
+            .iter()
+            .position(|((cached_icon, cached_size), _)| {
+                cached_icon == icon && *cached_size == pixel_size
+            })?;
+        let entry = self.entries.remove(index)?;
+        let texture = entry.1.clone();
+        self.entries.push_back(entry);
+        Some(texture)
+    }
+
+    fn insert(&mut self, icon: String, pixel_size: i32, texture: gdk::Texture) {
+        if self.entries.len() >= TEXTURE_CACHE_SIZE {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(((icon, pixel_size), texture));
+    }
+}
+
+/// Where to get an icon from
+enum IconSource {
+    Name(String),
+    File(PathBuf),
+    Data(Vec<u8>),
+}
+
+impl IconSource {
+    /// Icons can be names from the icon theme, absolute paths, `file://` URIs or `data:` URIs
+    fn parse(icon: &str) -> Option<Self> {
+        if icon.starts_with("data:") {
+            decode_data_uri(icon).map(Self::Data)
+        } else if icon.starts_with("file://") {
+            glib::filename_from_uri(icon)
+                .ok()
+                .map(|(path, _)| Self::File(path))
+        } else if Path::new(icon).is_absolute() {
+            Some(Self::File(icon.into()))
+        } else if !icon.is_empty() {
+            Some(Self::Name(icon.to_string()))
+        } else {
+            None
+        }
+    }
+}
+
+/// The content of a `data:` URI, which is either base64 or percent encoded
+fn decode_data_uri(uri: &str) -> Option<Vec<u8>> {
+    let (header, data) = uri.strip_prefix("data:")?.split_once(',')?;
+    if header.ends_with(";base64") {
+        Some(glib::base64_decode(data))
+    } else {
+        // The data is binary, so it's not unescaped to a string
+        glib::Uri::unescape_bytes(data, None)
+            .map(|data| data.to_vec())
+            .ok()
+    }
+}
+
+/// Builds an image of the first of the icons that can be found, or of a generic icon if none of
+/// them can be. `size` is in logical pixels, images are loaded at the size in physical pixels so
+/// that they stay sharp on scaled outputs, and again when the image is moved to an output with
+/// another scale.
+///
+/// Images are loaded in the background so that building rows doesn't wait for them, with a
+/// placeholder shown until then. Files are shown by their thumbnail if there is one.
+pub fn build_image(icons: &[&str], size: i32) -> gtk::Image {
+    let image = gtk::Image::builder()
+        .name(style_names::MATCH)
+        .pixel_size(size)
+        .build();
+
+    let icons: Vec<String> = icons.iter().map(|icon| icon.to_string()).collect();
+    show_icon(&image, &icons);
+    image.connect_scale_factor_notify(move |image| show_icon(image, &icons));
+
+    image
+}
+
+/// The size in physical pixels the image shows its icon at
+fn physical_size(image: &gtk::Image) -> i32 {
+    image.pixel_size() * image.scale_factor()
+}
+
+/// Shows the first of the icons that can be found in the image
+fn show_icon(image: &gtk::Image, icons: &[String]) {
+    let icon_theme = gtk::IconTheme::for_display(&image.display());
+    let pixel_size = physical_size(image);
+
+    let mut icons = icons.iter().map(String::as_str).chain([GENERIC_ICON]);
+
+    // Whatever is at hand is shown right away
+    for icon in icons.by_ref() {
+        match IconSource::parse(icon) {
+            Some(IconSource::Name(name)) if icon_theme.has_icon(&name) => {
+                image.set_icon_name(Some(&name));
+                return;
+            }
+            Some(IconSource::Name(_)) | None => (),
+            Some(source) => {
+                if let Some(texture) =
+                    TEXTURE_CACHE.with(|cache| cache.borrow_mut().get(icon, pixel_size))
+                {
+                    image.set_paintable(Some(&texture));
+                    return;
+                }
+
+                if icon_theme.has_icon(LOADING_ICON) {
+                    image.set_icon_name(Some(LOADING_ICON));
+                }
+
+                let rest: Vec<String> = icons.map(String::from).collect();
+                glib::MainContext::default().spawn_local(load_image(
+                    image.downgrade(),
+                    (icon.to_string(), source),
+                    rest,
+                    pixel_size,
+                ));
+                return;
+            }
+        }
+    }
+}
+
+/// Shows the first of the icons that loads in the image, or the generic icon if none of them
+/// does, if the image is still around and at the same scale by then
+async fn load_image(
+    image: glib::WeakRef<gtk::Image>,
+    first: (String, IconSource),
+    rest: Vec<String>,
+    pixel_size: i32,
+) {
+    let rest = rest
+        .into_iter()
+        .filter_map(|icon| IconSource::parse(&icon).map(|source| (icon, source)));
+
+    for (icon, source) in [first].into_iter().chain(rest) {
+        let texture = match &source {
+            IconSource::Name(name) => {
+                let Some(image) = image.upgrade() else {
+                    return;
+                };
+                if gtk::IconTheme::for_display(&image.display()).has_icon(name) {
+                    image.set_icon_name(Some(name));
+                    return;
+                }
+                continue;
+            }
+            _ => match TEXTURE_CACHE.with(|cache| cache.borrow_mut().get(&icon, pixel_size)) {
+                Some(texture) => Some(texture),
+                None => load_texture(&source, pixel_size).await,
+            },
+        };
+
+        if let Some(texture) = texture {
+            TEXTURE_CACHE
+                .with(|cache| cache.borrow_mut().insert(icon, pixel_size, texture.clone()));
+            // Loaded again for the new scale otherwise
+            if let Some(image) = image
+                .upgrade()
+                .filter(|image| physical_size(image) == pixel_size)
+            {
+                image.set_paintable(Some(&texture));
+            }
+            return;
+        }
+    }
+
+    // So that the loading icon doesn't stay, even if the icon theme has no generic icon
+    if let Some(image) = image.upgrade() {
+        image.set_icon_name(Some(GENERIC_ICON));
+    }
+}
+
+async fn load_texture(source: &IconSource, pixel_size: i32) -> Option<gdk::Texture> {
+    let pixbuf = match source {
+        IconSource::Name(_) => return None,
+        IconSource::File(path) => {
+            let file = gio::File::for_path(path);
+            let mut pixbuf = None;
+            for thumbnail in thumbnail_paths(&file, pixel_size) {
+                match load_pixbuf(gio::File::for_path(&thumbnail), pixel_size).await {
+                    Ok(thumbnail_pixbuf) => {
+                        if is_fresh(&thumbnail_pixbuf, &file).await {
+                            pixbuf = Some(Ok(thumbnail_pixbuf));
+                            break;
+                        }
+                        debug!("Skipping outdated thumbnail {:?}", thumbnail);
+                    }
+                    // Most files don't have a thumbnail of every size
+                    Err(why) if why.matches(gio::IOErrorEnum::NotFound) => (),
+                    Err(why) => debug!("Failed to load thumbnail {:?}: {}", thumbnail, why),
+                }
+            }
+
+            match pixbuf {
+                Some(pixbuf) => pixbuf,
+                None => load_pixbuf(file, pixel_size).await,
+            }
+        }
+        IconSource::Data(data) => {
+            let stream = gio::MemoryInputStream::from_bytes(&glib::Bytes::from(data));
+            Pixbuf::from_stream_at_scale_future(&stream, pixel_size, pixel_size, true).await
+        }
+    };
+
+    match pixbuf {
+        Ok(pixbuf) => Some(gdk::Texture::for_pixbuf(&pixbuf)),
+        Err(why) => {
+            debug!("Failed to load icon: {}", why);
+            None
+        }
+    }
+}
+
+async fn load_pixbuf(file: gio::File, pixel_size: i32) -> Result<Pixbuf, glib::Error> {
+    let stream = file.read_future(glib::Priority::DEFAULT).await?;
+    Pixbuf::from_stream_at_scale_future(&stream, pixel_size, pixel_size, true).await
+}
+
+/// The paths the thumbnails of the file would have in the XDG thumbnail cache at the sizes that
+/// are at least as large as `pixel_size`, smallest first. Whether they exist is only found out
+/// when loading them, so that the main thread doesn't wait for the disk.
+fn thumbnail_paths(file: &gio::File, pixel_size: i32) -> Vec<PathBuf> {
+    let uri = file.uri();
+    let Some(hash) = glib::compute_checksum_for_string(glib::ChecksumType::Md5, uri.as_str())
+    else {
+        return Vec::new();
+    };
+    let thumbnails_dir = glib::user_cache_dir().join("thumbnails");
+
+    THUMBNAIL_SIZES
+        .iter()
+        .filter(|(size, _)| *size >= pixel_size)
+        .map(|(_, dir)| thumbnails_dir.join(dir).join(format!("{}.png", hash)))
+        .collect()
+}
+
+/// Whether the thumbnail was made from the current version of the file, going by the
+/// modification time stored in it
+async fn is_fresh(thumbnail: &Pixbuf, file: &gio::File) -> bool {
+    let modified = file
+        .query_info_future(
+            gio::FILE_ATTRIBUTE_TIME_MODIFIED,
+            gio::FileQueryInfoFlags::NONE,
+            glib::Priority::DEFAULT,
+        )
+        .await
+        .ok()
+        .map(|info| info.attribute_uint64(gio::FILE_ATTRIBUTE_TIME_MODIFIED));
+    let thumbnail_modified = thumbnail
+        .option("tEXt::Thumb::MTime")
+        .and_then(|mtime| mtime.parse::<u64>().ok());
+
+    match (modified, thumbnail_modified) {
+        (Some(modified), Some(thumbnail_modified)) => modified == thumbnail_modified,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture() -> gdk::Texture {
+        gdk::MemoryTexture::new(
+            1,
+            1,
+            gdk::MemoryFormat::R8g8b8a8,
+            &glib::Bytes::from_static(&[0; 4]),
+            4,
+        )
+        .upcast()
+    }
+
+    #[test]
+    fn the_least_recently_used_texture_is_evicted() {
+        let mut cache = TextureCache::default();
+        let texture = texture();
+        for index in 0..TEXTURE_CACHE_SIZE {
+            cache.insert(index.to_string(), 32, texture.clone());
+        }
+
+        // Using the oldest one makes the one after it the least recently used
+        assert!(cache.get("0", 32).is_some());
+        cache.insert("new".to_string(), 32, texture.clone());
+
+        assert!(cache.get("1", 32).is_none());
+        assert!(cache.get("0", 32).is_some());
+        assert!(cache.get("2", 32).is_some());
+        assert!(cache.get("new", 32).is_some());
+        assert_eq!(cache.entries.len(), TEXTURE_CACHE_SIZE);
+    }
+
+    #[test]
+    fn textures_are_cached_per_size() {
+        let mut cache = TextureCache::default();
+        cache.insert("icon".to_string(), 32, texture());
+
+        assert!(cache.get("icon", 32).is_some());
+        assert!(cache.get("icon", 64).is_none());
+        assert!(cache.get("other", 32).is_none());
+    }
+
+    #[test]
+    fn icons_are_parsed_by_their_form() {
+        assert!(matches!(
+            IconSource::parse("firefox"),
+            Some(IconSource::Name(name)) if name == "firefox"
+        ));
+        assert!(matches!(
+            IconSource::parse("/usr/share/pixmaps/firefox.png"),
+            Some(IconSource::File(path)) if path == Path::new("/usr/share/pixmaps/firefox.png")
+        ));
+        assert!(matches!(
+            IconSource::parse("file:///tmp/an%20icon.png"),
+            Some(IconSource::File(path)) if path == Path::new("/tmp/an icon.png")
+        ));
+        assert!(matches!(
+            IconSource::parse("data:image/png;base64,aGk="),
+            Some(IconSource::Data(data)) if data == b"hi"
+        ));
+        assert!(IconSource::parse("").is_none());
+        assert!(IconSource::parse("file://not a uri").is_none());
+    }
+
+    #[test]
+    fn data_uris_are_decoded() {
+        assert_eq!(
+            decode_data_uri("data:image/png;base64,aGk="),
+            Some(b"hi".to_vec())
+        );
+        assert_eq!(
+            decode_data_uri("data:image/svg+xml,%3Csvg%2F%3E"),
+            Some(b"<svg/>".to_vec())
+        );
+        assert_eq!(decode_data_uri("data:,plain"), Some(b"plain".to_vec()));
+        // Binary data isn't valid UTF-8
+        assert_eq!(
+            decode_data_uri("data:application/octet-stream,%00%FF"),
+            Some(vec![0x00, 0xff])
+        );
+    }
+
+    #[test]
+    fn malformed_data_uris_are_rejected() {
+        assert_eq!(decode_data_uri("data:image/png;base64"), None);
+        assert_eq!(decode_data_uri("image/png;base64,aGk="), None);
+        assert_eq!(decode_data_uri("data:,%ZZ"), None);
+    }
+}
@@ -1,11 +1,20 @@
 mod config;
+mod highlight;
+mod icons;
 mod plugins;
+mod post_run;
+mod results;
+mod status;
+mod theme;
 mod types;
 mod ui;
 
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
 
-use anyrun_interface::PluginRef as Plugin;
+use anyrun_interface::{Activation, ActivationSource, Modifiers, PluginRef as Plugin};
 use clap::Parser;
 use gtk::{
     gdk, gio,
@@ -17,12 +26,20 @@ use nix::unistd;
 
 use config::*;
 use plugins::*;
+use results::*;
+use status::*;
 use types::*;
 use ui::*;
-use wl_clipboard_rs::copy;
 
 fn main() -> Result<glib::ExitCode, glib::Error> {
     env_logger::init();
+
+    let args = Args::parse();
+    if let Some(InternalCommand::WaitForNotification) = args.command {
+        post_run::wait_for_notification()?;
+        return Ok(glib::ExitCode::SUCCESS);
+    }
+
     gtk::init().expect("Failed to initialize GTK.");
 
     let app = gtk::Application::new(Some(APP_ID), Default::default());
@@ -34,7 +51,6 @@ fn main() -> Result<glib::ExitCode, glib::Error> {
 
     let app_state = gio::Settings::new(APP_ID);
 
-    let args = Args::parse();
     let config_dir = determine_config_dir(&args.config_dir);
     let (mut config, error_label) = load_config(&config_dir);
     config.merge_opt(args.config);
@@ -62,13 +78,28 @@ fn main() -> Result<glib::ExitCode, glib::Error> {
         .map(|filename| load_plugin(filename, &config_dir))
         .collect();
 
+    let match_template = load_match_template(&config_dir);
+
+    let layer_shell = config.window_type.uses_layer_shell();
+    if !layer_shell {
+        info!("Not using layer-shell, opening a regular window.");
+    }
+
     let runtime_data = Rc::new(RefCell::new(RuntimeData {
         exclusive: None,
+        nav_stack: Vec::new(),
+        pending_selection: None,
+        highlight_attributes: String::new(),
+        confirmation: None,
+        form: None,
         post_run_action: PostRunAction::None,
         config,
         error_label,
         config_dir,
+        match_template,
         geometry,
+        layer_shell,
+        status_bar: None,
         list_store,
         plugins,
         app_state,
@@ -85,56 +116,37 @@ fn main() -> Result<glib::ExitCode, glib::Error> {
 }
 
 fn handle_post_run_action(runtime_data: Rc<RefCell<RuntimeData>>) {
-    if let PostRunAction::Copy(bytes) = &runtime_data.borrow().post_run_action {
-        match unsafe { unistd::fork() } {
+    let runtime_data = runtime_data.borrow();
+
+    match &runtime_data.post_run_action {
+        PostRunAction::Copy(clipboard_copy) => match unsafe { unistd::fork() } {
             Ok(unistd::ForkResult::Parent { .. }) => {
                 info!("Child spawned to serve copy requests.");
             }
             Ok(unistd::ForkResult::Child) => {
-                serve_copy_requests(bytes);
+                post_run::serve_copy_requests(clipboard_copy);
             }
             Err(why) => {
                 error!("Failed to fork for copy sharing: {}", why);
             }
-        }
+        },
+        PostRunAction::Launch(launch) => post_run::launch(launch, &runtime_data.config),
+        PostRunAction::Open(open) => post_run::open(open),
+        PostRunAction::Notify(notify) => post_run::notify(notify, &runtime_data.config),
+        PostRunAction::None => (),
     }
 }
 
-fn serve_copy_requests(bytes: &[u8]) {
-    let mut opts = copy::Options::new();
-    opts.foreground(true);
-    opts.copy(
-        copy::Source::Bytes(bytes.to_vec().into_boxed_slice()),
-        copy::MimeType::Autodetect,
-    )
-    .expect("Failed to serve copy bytes");
-}
-
 fn activate(app: &impl IsA<gtk::Application>, runtime_data: Rc<RefCell<RuntimeData>>) {
     load_custom_css(runtime_data.clone());
 
-    let main_list = Rc::new(
-        gtk::ListBox::builder()
-            .selection_mode(gtk::SelectionMode::Browse)
-            .name(style_names::MAIN)
-            .build(),
-    );
+    let results = Results::new(runtime_data.clone());
+    if runtime_data.borrow().config.show_status {
+        runtime_data.borrow_mut().status_bar = Some(StatusBar::new());
+    }
 
     let list_store = runtime_data.clone().borrow().list_store.clone();
 
-    main_list.bind_model(
-        Some(&list_store),
-        clone!(@strong runtime_data => move |match_row| {
-            build_match_box(
-                runtime_data.clone(),
-                match_row
-                    .clone()
-                    .downcast::<GMatch>()
-                    .expect("Can't downcast glib::Object to GMatch"),
-            )
-        }),
-    );
-
     let app_state = runtime_data.borrow().app_state.clone();
 
     let entry = Rc::new(
@@ -150,36 +162,82 @@ fn activate(app: &impl IsA<gtk::Application>, runtime_data: Rc<RefCell<RuntimeDa
     }
 
     list_store.connect_items_changed(
-        clone!(@weak entry, @weak main_list, @weak runtime_data => move |_, _, _, _| {
-            main_list.select_row(main_list.row_at_index(0).as_ref())
+        clone!(@strong results, @weak runtime_data => move |list_store, _, _, _| {
+            results.set_layout(layout_in_use(&runtime_data.borrow()));
+
+            let pending_selection = runtime_data.borrow().pending_selection;
+            match pending_selection {
+                Some(index) if index < list_store.n_items() => {
+                    runtime_data.borrow_mut().pending_selection = None;
+                    results.select(index)
+                }
+                _ => results.select(0),
+            }
         }),
     );
 
     let window = Rc::new(setup_main_window(app, runtime_data.clone()));
 
+    let plugins = runtime_data.clone().borrow().plugins.clone();
+
+    let exclusive_chip = Rc::new(build_exclusive_chip());
+    let preview = Rc::new(build_preview());
+    if runtime_data.borrow().config.shows_preview() {
+        results.connect_selection_changed(
+            clone!(@strong preview, @strong runtime_data => move |row_id| {
+                update_preview(&preview, row_id, runtime_data.clone())
+            }),
+        );
+    }
+
+    let prompts = Prompts {
+        confirm_bar: Rc::new(build_confirm_bar()),
+        form_view: Rc::new(build_form_view()),
+    };
+
+    let on_back = clone!(@strong runtime_data, @strong exclusive_chip, @strong entry, @strong plugins => move || {
+        navigate_back(runtime_data.clone(), entry.clone(), &exclusive_chip, &plugins)
+    });
+
     let entry_eck = gtk::EventControllerKey::new();
-    connect_entry_key_press_events(entry.clone(), entry_eck, window.clone());
+    connect_entry_key_press_events(
+        entry.clone(),
+        entry_eck,
+        window.clone(),
+        on_back.clone(),
+        clone!(@strong runtime_data, @strong exclusive_chip, @strong entry, @strong plugins => move || {
+            navigate_up(runtime_data.clone(), entry.clone(), &exclusive_chip, &plugins)
+        }),
+        clone!(@strong runtime_data, @strong results, @strong entry => move || {
+            results.selected().is_some_and(|row_id| {
+                handle_completion(row_id, &*entry, runtime_data.clone())
+            })
+        }),
+    );
 
     let window_eck = gtk::EventControllerKey::new();
-    connect_window_key_press_events(window.clone(), window_eck, window.clone());
-
-    let plugins = runtime_data.clone().borrow().plugins.clone();
+    connect_window_key_press_events(window.clone(), window_eck, window.clone(), on_back);
 
     setup_entry_changed(entry.clone(), runtime_data.clone(), plugins.clone());
+    if runtime_data.borrow().config.layout == Layout::Bar {
+        setup_bar_navigation(entry.clone(), results.clone());
+    }
     setup_entry_activated(
         entry.clone(),
-        main_list.clone(),
+        results.clone(),
         window.clone(),
         runtime_data.clone(),
-        plugins.clone(),
+        exclusive_chip.clone(),
+        prompts.clone(),
     );
 
     setup_row_activated(
-        main_list.clone(),
+        &results,
         window.clone(),
         runtime_data.clone(),
         entry.clone(),
-        plugins.clone(),
+        exclusive_chip.clone(),
+        prompts.clone(),
     );
 
     if runtime_data.borrow().config.show_results_immediately {
@@ -190,8 +248,13 @@ fn activate(app: &impl IsA<gtk::Application>, runtime_data: Rc<RefCell<RuntimeDa
         window.clone(),
         runtime_data.clone(),
         entry.clone(),
-        main_list.clone(),
+        exclusive_chip.clone(),
+        &prompts,
+        preview.clone(),
+        &results,
     );
+    setup_placement(window.clone(), runtime_data.clone());
+    setup_close_triggers(window.clone(), runtime_data.clone());
 
     window.present();
 }
@@ -202,43 +265,313 @@ fn setup_entry_changed(
     plugins: Vec<Plugin>,
 ) {
     entry.connect_changed(move |e| {
-        runtime_data.borrow_mut().exclusive = None;
         refresh_matches(&e.text(), &plugins, runtime_data.clone());
     });
 }
 
+/// Lets `Left` and `Right` move the selection along the bar, like in dmenu, while the cursor is at
+/// the end of the entry text
+fn setup_bar_navigation(entry: Rc<gtk::SearchEntry>, results: Results) {
+    let entry_eck = gtk::EventControllerKey::new();
+    entry_eck.set_propagation_phase(gtk::PropagationPhase::Capture);
+    entry_eck.connect_key_pressed(clone!(@strong entry => move |_, keyval, _, state| {
+        let at_end = entry.position() == entry.text().chars().count() as i32;
+        let moved = at_end
+            && modifiers_from_state(state) == Modifiers::default()
+            && match keyval {
+                gdk::Key::Right => results.move_selection(1),
+                gdk::Key::Left => results.move_selection(-1),
+                _ => false,
+            };
+
+        if moved {
+            glib::Propagation::Stop
+        } else {
+            glib::Propagation::Proceed
+        }
+    }));
+    entry.add_controller(entry_eck);
+}
+
 fn setup_entry_activated(
     entry: Rc<gtk::SearchEntry>,
-    main_list: Rc<gtk::ListBox>,
+    results: Results,
     window: Rc<gtk::ApplicationWindow>,
     runtime_data: Rc<RefCell<RuntimeData>>,
-    plugins: Vec<Plugin>,
+    exclusive_chip: Rc<gtk::Box>,
+    prompts: Prompts,
 ) {
-    entry.connect_activate(move |e| {
-        if let Some(row) = main_list.selected_row() {
-            handle_selection_activation(
-                row.index().try_into().unwrap(),
+    let activate = Rc::new(move |entry: &gtk::SearchEntry, modifiers: Modifiers| {
+        if let Some(row_id) = results.selected() {
+            activate_row(
+                row_id,
+                Activation {
+                    source: ActivationSource::Entry,
+                    modifiers,
+                    button: 0,
+                },
                 window.clone(),
+                entry,
                 runtime_data.clone(),
-                |_| refresh_matches(&e.text(), &plugins, runtime_data.clone()),
+                exclusive_chip.clone(),
+                prompts.clone(),
             )
         }
     });
+
+    // Looked at before the entry handles the key, so that the modifiers of `Enter` are known
+    let entry_eck = gtk::EventControllerKey::new();
+    entry_eck.set_propagation_phase(gtk::PropagationPhase::Capture);
+    entry_eck.connect_key_pressed(
+        clone!(@strong entry, @strong activate => move |_, keyval, _, state| {
+            let modifiers = modifiers_from_state(state);
+            if matches!(keyval, gdk::Key::Return | gdk::Key::KP_Enter | gdk::Key::ISO_Enter)
+                && modifiers != Modifiers::default()
+            {
+                activate(&entry, modifiers);
+                glib::Propagation::Stop
+            } else {
+                glib::Propagation::Proceed
+            }
+        }),
+    );
+    entry.add_controller(entry_eck);
+
+    entry.connect_activate(move |e| activate(e, Modifiers::default()));
 }
 
 fn setup_row_activated(
-    main_list: Rc<gtk::ListBox>,
+    results: &Results,
     window: Rc<gtk::ApplicationWindow>,
     runtime_data: Rc<RefCell<RuntimeData>>,
     entry: Rc<gtk::SearchEntry>,
-    plugins: Vec<Plugin>,
+    exclusive_chip: Rc<gtk::Box>,
+    prompts: Prompts,
 ) {
-    main_list.connect_row_activated(move |_, row| {
-        handle_selection_activation(
-            row.index().try_into().unwrap(),
+    let activate = Rc::new(move |row_id: usize, activation: Activation| {
+        activate_row(
+            row_id,
+            activation,
             window.clone(),
+            &entry,
             runtime_data.clone(),
-            |_| refresh_matches(&entry.text(), &plugins, runtime_data.clone()),
+            exclusive_chip.clone(),
+            prompts.clone(),
         )
     });
+
+    // The button and modifiers of the current click, to tell mouse activations apart
+    let last_click = Rc::new(Cell::new(None));
+
+    let main_list = results.list.clone();
+    let gesture_click = track_clicks(last_click.clone());
+    gesture_click.connect_released(
+        clone!(@strong main_list, @strong activate => move |gesture, _, _, y| {
+            // Rows are only activated by the primary button
+            if gesture.current_button() == gdk::BUTTON_MIDDLE {
+                if let Some(row) = main_list.row_at_y(y as i32) {
+                    activate(
+                        row.index().try_into().unwrap(),
+                        Activation {
+                            source: ActivationSource::Mouse,
+                            modifiers: modifiers_from_state(gesture.current_event_state()),
+                            button: gdk::BUTTON_MIDDLE,
+                        },
+                    );
+                }
+            }
+        }),
+    );
+    main_list.add_controller(gesture_click);
+
+    main_list.connect_row_activated(
+        clone!(@strong last_click, @strong activate => move |list, row| {
+            activate(row.index().try_into().unwrap(), click_activation(&last_click, list))
+        }),
+    );
+
+    // Middle clicks on their rows and cells activate the matches through the same signal
+    if let Some(template_list) = &results.template_list {
+        template_list.add_controller(track_clicks(last_click.clone()));
+        template_list.connect_activate(
+            clone!(@strong last_click, @strong activate => move |list, position| {
+                activate(position as usize, click_activation(&last_click, list))
+            }),
+        );
+    }
+    results
+        .grid
+        .add_controller(track_clicks(last_click.clone()));
+    results.grid.connect_activate(
+        clone!(@strong last_click, @strong activate => move |grid, position| {
+            activate(position as usize, click_activation(&last_click, grid))
+        }),
+    );
+    results.bar.add_controller(track_clicks(last_click.clone()));
+    results.bar.connect_activate(move |bar, position| {
+        activate(position as usize, click_activation(&last_click, bar))
+    });
+}
+
+/// Remembers the button and modifiers of clicks on the view in `last_click` until the view has
+/// handled them
+fn track_clicks(last_click: Rc<Cell<Option<(u32, gdk::ModifierType)>>>) -> gtk::GestureClick {
+    let gesture_click = gtk::GestureClick::builder()
+        .button(0)
+        .propagation_phase(gtk::PropagationPhase::Capture)
+        .build();
+    gesture_click.connect_pressed(clone!(@strong last_click => move |gesture, _, _, _| {
+        last_click.set(Some((gesture.current_button(), gesture.current_event_state())));
+    }));
+    gesture_click.connect_released(move |_, _, _, _| {
+        // The view activates the match after this handler, so forget the click only afterwards
+        glib::idle_add_local_once(clone!(@strong last_click => move || last_click.set(None)));
+    });
+    gesture_click
+}
+
+/// How a match of the view was activated, by the tracked click or else from the keyboard
+fn click_activation(
+    last_click: &Cell<Option<(u32, gdk::ModifierType)>>,
+    view: &impl WidgetExt,
+) -> Activation {
+    match last_click.take() {
+        Some((button, state)) => Activation {
+            source: ActivationSource::Mouse,
+            modifiers: modifiers_from_state(state),
+            button,
+        },
+        None => Activation {
+            source: ActivationSource::Row,
+            modifiers: keyboard_modifiers(view),
+            button: 0,
+        },
+    }
+}
+
+fn activate_row(
+    row_id: usize,
+    activation: Activation,
+    window: Rc<gtk::ApplicationWindow>,
+    entry: &gtk::SearchEntry,
+    runtime_data: Rc<RefCell<RuntimeData>>,
+    exclusive_chip: Rc<gtk::Box>,
+    prompts: Prompts,
+) {
+    handle_selection_activation(
+        row_id,
+        activation,
+        window.clone(),
+        entry,
+        runtime_data.clone(),
+        on_refresh(entry, runtime_data.clone(), &exclusive_chip),
+    );
+    show_prompts(window, entry, runtime_data, exclusive_chip, prompts);
+}
+
+/// The callback for handled selections to update the exclusive chip with, and the matches if
+/// called with `true`. Changing the entry text refreshes the matches already.
+fn on_refresh<'a>(
+    entry: &'a gtk::SearchEntry,
+    runtime_data: Rc<RefCell<RuntimeData>>,
+    exclusive_chip: &'a gtk::Box,
+) -> impl FnMut(bool) + 'a {
+    move |refresh| {
+        update_exclusive_chip(exclusive_chip, runtime_data.clone());
+        if refresh {
+            let plugins = runtime_data.borrow().plugins.clone();
+            refresh_matches(&entry.text(), &plugins, runtime_data.clone())
+        }
+    }
+}
+
+/// Shows the confirm bar or the form a plugin has asked for, and lets the plugin handle the
+/// answer
+fn show_prompts(
+    window: Rc<gtk::ApplicationWindow>,
+    entry: &gtk::SearchEntry,
+    runtime_data: Rc<RefCell<RuntimeData>>,
+    exclusive_chip: Rc<gtk::Box>,
+    prompts: Prompts,
+) {
+    let confirmation = runtime_data
+        .borrow()
+        .confirmation
+        .as_ref()
+        .map(|confirmation| {
+            (
+                confirmation.prompt.clone(),
+                confirmation.confirm_label.clone(),
+            )
+        });
+    if let Some((prompt, confirm_label)) = confirmation {
+        show_confirm_bar(
+            &prompts.confirm_bar.clone(),
+            &prompt,
+            confirm_label.as_deref(),
+            clone!(@strong entry => move |confirmed| {
+                hide_confirm_bar(&prompts.confirm_bar);
+                entry.grab_focus();
+
+                if confirmed {
+                    handle_confirmed_activation(
+                        window.clone(),
+                        &entry,
+                        runtime_data.clone(),
+                        on_refresh(&entry, runtime_data.clone(), &exclusive_chip),
+                    );
+                    show_prompts(
+                        window.clone(),
+                        &entry,
+                        runtime_data.clone(),
+                        exclusive_chip.clone(),
+                        prompts.clone(),
+                    );
+                } else {
+                    runtime_data.borrow_mut().confirmation = None;
+                }
+            }),
+        );
+        return;
+    }
+
+    let form = runtime_data.borrow().form.as_ref().map(|form| {
+        (
+            form.title.clone(),
+            form.fields.clone(),
+            form.submit_label.clone(),
+        )
+    });
+    if let Some((title, fields, submit_label)) = form {
+        show_form(
+            &prompts.form_view.clone(),
+            &title,
+            &fields,
+            submit_label.as_deref(),
+            clone!(@strong entry => move |values| {
+                hide_form(&prompts.form_view);
+                entry.grab_focus();
+
+                match values {
+                    Some(values) => {
+                        handle_form_submission(
+                            values,
+                            window.clone(),
+                            &entry,
+                            runtime_data.clone(),
+                            on_refresh(&entry, runtime_data.clone(), &exclusive_chip),
+                        );
+                        show_prompts(
+                            window.clone(),
+                            &entry,
+                            runtime_data.clone(),
+                            exclusive_chip.clone(),
+                            prompts.clone(),
+                        );
+                    }
+                    None => runtime_data.borrow_mut().form = None,
+                }
+            }),
+        );
+    }
 }
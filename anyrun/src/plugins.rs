@@ -1,23 +1,46 @@
-use std::{cell::RefCell, env, path::PathBuf, rc::Rc, time::Duration};
+use std::{
+    cell::RefCell,
+    env, fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 use anyrun_interface::{Match, PluginRef as Plugin, PollResult};
 #[allow(unused_imports)]
 use log::*;
 
-use crate::{config::*, types::GMatch};
+use crate::{
+    config::*,
+    highlight::{highlight_markup, match_ranges},
+    icons::build_image,
+    types::GMatch,
+};
 
 use gtk::{
     gio,
     glib::{self, clone},
+    pango,
     prelude::*,
 };
 
+/// Checks that the markup can be parsed, as GTK would show nothing for it otherwise
+pub fn is_valid_markup(markup: &str) -> bool {
+    match pango::parse_markup(markup, '\0') {
+        Ok(_) => true,
+        Err(why) => {
+            warn!("Showing invalid markup {:?} as plain text: {}", markup, why);
+            false
+        }
+    }
+}
+
 pub fn build_label(name: &str, use_markup: bool, label: &str) -> gtk::Label {
     gtk::Label::builder()
         .name(name)
         .wrap(true)
         .xalign(0.0)
-        .use_markup(use_markup)
+        .use_markup(use_markup && is_valid_markup(label))
         .halign(gtk::Align::Start)
         .valign(gtk::Align::Center)
         .vexpand(true)
@@ -25,19 +48,97 @@ pub fn build_label(name: &str, use_markup: bool, label: &str) -> gtk::Label {
         .build()
 }
 
-pub fn build_image(icon: &str) -> gtk::Image {
-    let mut match_image = gtk::Image::builder()
-        .name(style_names::MATCH)
-        .pixel_size(32);
+/// The CSS classes of the row of a match: `plugin-<name>` for its plugin, `first` for the first
+/// match of the plugin, and the ones the plugin adds that are valid class names
+pub fn match_classes(runtime_data: &RuntimeData, gmatch: &GMatch) -> Vec<String> {
+    let plugin = runtime_data
+        .plugins
+        .get(gmatch.get_plugin_id() as usize)
+        .expect("Can't get plugin by id");
 
-    let path = PathBuf::from(icon);
+    // The name was stored with the match, so the plugin isn't asked for it for every row
+    let plugin_name: String = gmatch
+        .get_plugin_name()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
 
-    match_image = if path.is_absolute() {
-        match_image.file(path.to_string_lossy())
-    } else {
-        match_image.icon_name(icon)
+    let mut classes = vec![format!("plugin-{}", plugin_name)];
+    if gmatch.get_first() {
+        classes.push("first".to_string());
+    }
+    classes.extend(
+        plugin.classes()(gmatch.clone().into())
+            .into_iter()
+            .map(String::from)
+            .filter(|class| {
+                let valid = is_css_identifier(class);
+                if !valid {
+                    debug!("Ignoring invalid CSS class {:?} from a plugin", class);
+                }
+                valid
+            }),
+    );
+    classes
+}
+
+/// Whether the class can be selected as `.class` in CSS without escaping it
+fn is_css_identifier(class: &str) -> bool {
+    let name = class.strip_prefix('-').unwrap_or(class);
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || !c.is_ascii())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || !c.is_ascii())
+}
+
+/// Key of the classes `bind_cell` last added to a cell
+const CELL_CLASSES: &str = "anyrun-cell-classes";
+
+/// The name of the row template in the config directory
+const MATCH_TEMPLATE: &str = "match.ui";
+
+/// Reads the user's row template from the config directory, if there is one that builds a row
+pub fn load_match_template(config_dir: &Path) -> Option<glib::Bytes> {
+    let template_path = config_dir.join(MATCH_TEMPLATE);
+    if !template_path.exists() {
+        return None;
+    }
+
+    let template = match fs::read_to_string(&template_path) {
+        Ok(template) => template,
+        Err(why) => {
+            warn!("Failed to read row template {:?}: {}", template_path, why);
+            return None;
+        }
     };
-    match_image.build()
+
+    // Built once for an empty list item, so a broken template is reported here rather than
+    // for every row
+    let result = gtk::Builder::new().extend_with_template(
+        &glib::Object::new::<gtk::ListItem>(),
+        gtk::ListItem::static_type(),
+        &template,
+    );
+    match result {
+        Ok(()) => {
+            info!("Using row template from {:?}", template_path);
+            Some(glib::Bytes::from_owned(template))
+        }
+        Err(why) => {
+            warn!(
+                "Not using row template {:?}, falling back to the built-in rows: {}",
+                template_path, why
+            );
+            None
+        }
+    }
 }
 
 pub fn build_match_box(runtime_data: Rc<RefCell<RuntimeData>>, gmatch: GMatch) -> gtk::Widget {
@@ -62,7 +163,7 @@ pub fn build_match_box(runtime_data: Rc<RefCell<RuntimeData>>, gmatch: GMatch) -
 
     let plugin_info = plugin.info()();
 
-    let plugin_icon = build_image(&plugin_info.icon);
+    let plugin_icon = build_image(&[&plugin_info.icon], runtime_data.config.plugin_icon_size);
     plugin_icon.set_margin_start(4);
     plugin_icon.set_margin_end(8);
     plugin_info_box.append(&plugin_icon);
@@ -90,7 +191,10 @@ pub fn build_match_box(runtime_data: Rc<RefCell<RuntimeData>>, gmatch: GMatch) -
 
     if !runtime_data.config.hide_match_icons {
         if let Some(icon) = gmatch.get_icon() {
-            match_box.append(&build_image(&icon));
+            match_box.append(&build_image(
+                &[&icon, &gmatch.get_plugin_icon()],
+                runtime_data.config.match_icon_size,
+            ));
         }
     }
 
@@ -100,18 +204,12 @@ pub fn build_match_box(runtime_data: Rc<RefCell<RuntimeData>>, gmatch: GMatch) -
         .vexpand(true)
         .build();
 
-    vbox.append(&build_label(
-        style_names::MATCH_TITLE,
-        gmatch.get_use_pango(),
-        &gmatch.get_title(),
-    ));
-
-    if let Some(desc) = gmatch.get_description() {
-        vbox.append(&build_label(
-            style_names::MATCH_DESC,
-            gmatch.get_use_pango(),
-            &desc,
-        ));
+    vbox.append(&build_match_title(&gmatch));
+
+    if let Some(desc) = gmatch.get_description_markup() {
+        let desc_label = build_label(style_names::MATCH_DESC, false, "");
+        desc_label.set_markup(&desc);
+        vbox.append(&desc_label);
     }
 
     match_box.append(&vbox);
@@ -120,11 +218,147 @@ pub fn build_match_box(runtime_data: Rc<RefCell<RuntimeData>>, gmatch: GMatch) -
     hbox.into()
 }
 
-pub fn handle_matches(plugin_id: u64, matches: &[Match], list_store: gio::ListStore) {
+/// The title of a match as markup, with the words matching `input` highlighted. Markup from the
+/// plugin is left as it is, unless it's invalid.
+fn title_markup(gmatch: &GMatch, input: &str, highlight_attributes: &str) -> String {
+    let title = gmatch.get_title();
+    if gmatch.get_use_pango() && is_valid_markup(&title) {
+        return title;
+    }
+
+    highlight_markup(&title, &match_ranges(&title, input), highlight_attributes)
+}
+
+/// The description of a match as markup, escaped unless it's valid markup from the plugin
+fn description_markup(gmatch: &GMatch) -> Option<String> {
+    gmatch.get_description().map(|description| {
+        if gmatch.get_use_pango() && is_valid_markup(&description) {
+            description
+        } else {
+            glib::markup_escape_text(&description).into()
+        }
+    })
+}
+
+fn build_match_title(gmatch: &GMatch) -> gtk::Label {
+    let title = build_label(style_names::MATCH_TITLE, false, "");
+    title.set_markup(&gmatch.get_title_markup());
+    title
+}
+
+/// Shows a match in a cell from one of the `setup_*_cell` functions: sets its classes, swaps in
+/// `icon` for its first child and sets the title in its last one
+fn bind_cell(cell: &gtk::Box, runtime_data: &RuntimeData, gmatch: &GMatch, icon: gtk::Image) {
+    // Cells are reused for other matches, so the classes of the last one come off first. Classes
+    // the cell had already, like the ones GTK gives it, are left alone.
+    // SAFETY: The data under the key is only ever a `Vec<String>`
+    for class in unsafe { cell.steal_data::<Vec<String>>(CELL_CLASSES) }.unwrap_or_default() {
+        cell.remove_css_class(&class);
+    }
+    let mut classes = match_classes(runtime_data, gmatch);
+    classes.retain(|class| !cell.has_css_class(class));
+    for class in &classes {
+        cell.add_css_class(class);
+    }
+    unsafe { cell.set_data(CELL_CLASSES, classes) };
+
+    if let Some(old_icon) = cell.first_child() {
+        cell.remove(&old_icon);
+    }
+    cell.prepend(&icon);
+
+    if let Some(title) = cell.last_child().and_downcast::<gtk::Label>() {
+        title.set_markup(&gmatch.get_title_markup());
+    }
+}
+
+/// The cell of a match in the grid layout: its icon, or the one of its plugin, above its title
+pub fn setup_grid_cell() -> gtk::Box {
+    let vbox = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .width_request(96)
+        .spacing(4)
+        .build();
+    vbox.append(&gtk::Image::new());
+
+    let title = build_label(style_names::MATCH_TITLE, false, "");
+    title.set_halign(gtk::Align::Center);
+    title.set_justify(gtk::Justification::Center);
+    title.set_xalign(0.5);
+    title.set_lines(2);
+    title.set_max_width_chars(12);
+    title.set_ellipsize(pango::EllipsizeMode::End);
+    vbox.append(&title);
+
+    vbox
+}
+
+pub fn bind_grid_cell(cell: &gtk::Box, runtime_data: &RuntimeData, gmatch: &GMatch) {
+    let icon = build_image(
+        &[
+            &gmatch.get_icon().unwrap_or_default(),
+            &gmatch.get_plugin_icon(),
+        ],
+        runtime_data.config.match_icon_size * 3 / 2,
+    );
+    icon.set_visible(!runtime_data.config.hide_match_icons);
+
+    bind_cell(cell, runtime_data, gmatch, icon);
+}
+
+/// The cell of a match in the bar layout: its icon and its title on a single line
+pub fn setup_bar_cell() -> gtk::Box {
+    let hbox = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .spacing(4)
+        .build();
+    hbox.append(&gtk::Image::new());
+
+    let title = build_label(style_names::MATCH_TITLE, false, "");
+    title.set_wrap(false);
+    title.set_max_width_chars(30);
+    title.set_ellipsize(pango::EllipsizeMode::End);
+    hbox.append(&title);
+
+    hbox
+}
+
+pub fn bind_bar_cell(cell: &gtk::Box, runtime_data: &RuntimeData, gmatch: &GMatch) {
+    let icon = match gmatch.get_icon() {
+        Some(icon) if !runtime_data.config.hide_match_icons => build_image(
+            &[&icon, &gmatch.get_plugin_icon()],
+            runtime_data.config.match_icon_size / 2,
+        ),
+        _ => {
+            let icon = gtk::Image::new();
+            icon.set_visible(false);
+            icon
+        }
+    };
+
+    bind_cell(cell, runtime_data, gmatch, icon);
+}
+
+/// Adds the matches of the plugin to the list store, with their titles highlighted for `input`
+pub fn handle_matches(
+    plugin_id: u64,
+    plugin: &Plugin,
+    matches: &[Match],
+    list_store: gio::ListStore,
+    input: &str,
+    highlight_attributes: &str,
+) {
+    let plugin_info = plugin.info()();
+
     for (index, rmatch) in matches.iter().enumerate() {
         let gmatch = GMatch::from(rmatch.clone());
         gmatch.set_plugin_id(plugin_id);
+        gmatch.set_plugin_name(plugin_info.name.to_string());
+        gmatch.set_plugin_icon(plugin_info.icon.to_string());
         gmatch.set_first(index == 0);
+        // Worked out once here, for the rows of all layouts and templates to show
+        gmatch.set_title_markup(title_markup(&gmatch, input, highlight_attributes));
+        gmatch.set_description_markup(description_markup(&gmatch));
         list_store.append(&gmatch);
     }
 }
@@ -180,42 +414,74 @@ pub fn load_plugin(plugin_path: &PathBuf, config_dir: &PathBuf) -> Plugin {
 }
 
 pub fn refresh_matches(input: &str, plugins: &[Plugin], runtime_data: Rc<RefCell<RuntimeData>>) {
+    let highlight_attributes = runtime_data.borrow().highlight_attributes.clone();
+
     let list_store = runtime_data.borrow().list_store.clone();
     list_store.remove_all();
 
-    let mut exclusive_plugin_id = None;
-
-    let plugins_to_use = if let Some(exclusive_plugin) = runtime_data.borrow().exclusive.as_ref() {
-        exclusive_plugin_id = plugins
-            .iter()
-            .position(|p| p.info() == exclusive_plugin.info());
+    let exclusive_plugin_id = runtime_data.borrow().exclusive;
 
-        vec![*exclusive_plugin]
-    } else {
-        plugins.to_vec()
+    let plugins_to_use = match exclusive_plugin_id.and_then(|plugin_id| plugins.get(plugin_id)) {
+        Some(exclusive_plugin) => vec![*exclusive_plugin],
+        None => plugins.to_vec(),
     };
 
+    let status_bar = runtime_data.borrow().status_bar.clone();
+    let generation = status_bar
+        .as_ref()
+        .map(|status_bar| status_bar.start(&plugins_to_use));
+
     for (plugin_id, plugin) in plugins_to_use.iter().enumerate() {
+        let started = Instant::now();
         let id = plugin.get_matches()(input.into());
 
         glib::timeout_add_local(
             Duration::from_millis(1),
-            clone!(@strong list_store, @strong plugin => move || {
-                async_match(&plugin, id, |matches| {
-                    handle_matches(
-                        exclusive_plugin_id.unwrap_or(plugin_id) as u64,
-                        matches,
-                        list_store.clone(),
-                    )
-                })
+            clone!(@strong list_store, @strong plugin, @strong status_bar, @strong highlight_attributes, @to-owned input => move || {
+                async_match(
+                    &plugin,
+                    id,
+                    |matches| {
+                        handle_matches(
+                            exclusive_plugin_id.unwrap_or(plugin_id) as u64,
+                            &plugin,
+                            matches,
+                            list_store.clone(),
+                            &input,
+                            &highlight_attributes,
+                        );
+
+                        if let (Some(status_bar), Some(generation)) = (&status_bar, generation) {
+                            status_bar.finish(
+                                generation,
+                                plugin_id,
+                                matches.len(),
+                                started.elapsed(),
+                            );
+                        }
+                    },
+                    || {
+                        if let (Some(status_bar), Some(generation)) = (&status_bar, generation) {
+                            status_bar.cancel(generation, plugin_id);
+                        }
+                    },
+                )
             }),
         );
     }
 }
 
-pub fn async_match<F>(plugin: &Plugin, id: u64, mut func: F) -> glib::ControlFlow
+/// Polls the plugin for the matches of request `id`, calling `func` with them once they are
+/// ready, or `on_cancelled` if the plugin gave up on the request
+pub fn async_match<F, C>(
+    plugin: &Plugin,
+    id: u64,
+    mut func: F,
+    on_cancelled: C,
+) -> glib::ControlFlow
 where
     F: FnMut(&[Match]),
+    C: FnOnce(),
 {
     match plugin.poll_matches()(id) {
         PollResult::Ready(matches) => {
@@ -223,6 +489,24 @@ where
             glib::ControlFlow::Break
         }
         PollResult::Pending => glib::ControlFlow::Continue,
-        PollResult::Cancelled => glib::ControlFlow::Break,
+        PollResult::Cancelled => {
+            on_cancelled();
+            glib::ControlFlow::Break
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classes_are_css_identifiers() {
+        for class in ["warning", "urgent-2", "_private", "-gtk-like", "größe"] {
+            assert!(is_css_identifier(class), "{:?}", class);
+        }
+        for class in ["", "-", "2fa", "--", "a b", "a.b", "a:hover", "a{}"] {
+            assert!(!is_css_identifier(class), "{:?}", class);
+        }
     }
 }
@@ -0,0 +1,769 @@
+use std::{
+    cell::{Cell, RefCell},
+    env, fs,
+    io::{self, Read, Write},
+    os::{fd::AsRawFd, unix::process::CommandExt},
+    process::{Child, Command, Stdio},
+    rc::Rc,
+    time::Duration,
+};
+
+use anyrun_interface::ClipboardType;
+use gtk::{gio, glib, prelude::*};
+use log::*;
+use nix::unistd;
+use serde::{Deserialize, Serialize};
+use wl_clipboard_rs::copy;
+
+use crate::config::{ClipboardCopy, Config, Launch, Notify, Open, OpenTarget};
+
+const PORTAL_BUS_NAME: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_OBJECT_PATH: &str = "/org/freedesktop/portal/desktop";
+const OPEN_URI_INTERFACE: &str = "org.freedesktop.portal.OpenURI";
+const NOTIFICATIONS_BUS_NAME: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_OBJECT_PATH: &str = "/org/freedesktop/Notifications";
+const NOTIFICATIONS_INTERFACE: &str = "org.freedesktop.Notifications";
+
+/// How long to wait at most for an action of a notification without a timeout of its own, as
+/// servers keeping notifications around may never close them
+const NOTIFICATION_WAIT_LIMIT: Duration = Duration::from_secs(10 * 60);
+
+/// Serves the content in the foreground until the clipboard is taken by something else,
+/// the paste limit is reached or the timeout runs out
+pub fn serve_copy_requests(clipboard_copy: &ClipboardCopy) {
+    // Serving blocks the only thread of the forked child, so the alarm signal ends it instead.
+    // An alarm of 0 seconds would cancel it rather than going off right away.
+    if let Some(timeout) = clipboard_copy.timeout {
+        let secs = timeout.as_secs().clamp(1, u32::MAX.into()) as u32;
+        unistd::alarm::set(secs);
+    }
+
+    let mut opts = copy::Options::new();
+    opts.foreground(true)
+        .clipboard(match clipboard_copy.clipboard {
+            ClipboardType::Regular => copy::ClipboardType::Regular,
+            ClipboardType::Primary => copy::ClipboardType::Primary,
+            ClipboardType::Both => copy::ClipboardType::Both,
+        })
+        .serve_requests(match clipboard_copy.paste_limit {
+            // Serving 0 pastes would take the content off the clipboard right away
+            Some(limit) => copy::ServeRequests::Only(limit.max(1)),
+            None => copy::ServeRequests::Unlimited,
+        });
+
+    let sources = clipboard_copy
+        .offers
+        .iter()
+        .map(|(mime_type, bytes)| copy::MimeSource {
+            source: copy::Source::Bytes(bytes.clone().into_boxed_slice()),
+            mime_type: match mime_type {
+                Some(mime_type) => copy::MimeType::Specific(mime_type.clone()),
+                None => copy::MimeType::Autodetect,
+            },
+        })
+        .collect();
+
+    opts.copy_multi(sources)
+        .expect("Failed to serve copy bytes");
+}
+
+/// Spawns the program in a new session, so it is not taken down together with anyrun
+pub fn launch(launch: &Launch, config: &Config) {
+    if launch.argv.is_empty() {
+        error!("Plugin requested to launch an empty command");
+        return;
+    }
+
+    let mut argv = Vec::new();
+    if config.launch_with_systemd_run {
+        argv.extend(["systemd-run", "--user", "--scope", "--quiet"].map(String::from));
+    }
+    if launch.terminal {
+        argv.extend(terminal_command(config));
+    }
+    argv.extend(launch.argv.iter().cloned());
+
+    let mut command = Command::new(&argv[0]);
+    command
+        .args(&argv[1..])
+        .envs(launch.env.iter().map(|(key, value)| (key, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    if let Some(working_dir) = &launch.working_dir {
+        command.current_dir(working_dir);
+    }
+    if let Some(token) = &launch.activation_token {
+        command
+            .env("XDG_ACTIVATION_TOKEN", token)
+            .env("DESKTOP_STARTUP_ID", token);
+    }
+
+    new_session(&mut command);
+
+    match command.spawn() {
+        Ok(child) => info!("Launched {:?} with pid {}", launch.argv, child.id()),
+        Err(why) => error!("Failed to launch {:?}: {}", launch.argv, why),
+    }
+}
+
+fn new_session(command: &mut Command) {
+    // `setsid` is async-signal-safe, so it can be called between fork and exec
+    unsafe {
+        command.pre_exec(|| {
+            unistd::setsid()?;
+            Ok(())
+        });
+    }
+}
+
+fn terminal_command(config: &Config) -> Vec<String> {
+    if !config.terminal.is_empty() {
+        return config.terminal.clone();
+    }
+
+    match env::var("TERMINAL") {
+        Ok(terminal) if !terminal.is_empty() => vec![terminal, "-e".to_string()],
+        _ => vec!["xdg-terminal-exec".to_string()],
+    }
+}
+
+/// Opens the URI or file through the desktop portal, or with the default handler known to GIO
+/// if the portal is not available
+pub fn open(open: &Open) {
+    let result = gio::bus_get_sync(gio::BusType::Session, gio::Cancellable::NONE)
+        .and_then(|connection| open_with_portal(&connection, open));
+    if let Err(why) = result {
+        warn!(
+            "Failed to open through the desktop portal, using the default handler: {}",
+            why
+        );
+        open_with_default_handler(open);
+    }
+}
+
+fn open_with_portal(connection: &gio::DBusConnection, open: &Open) -> Result<(), glib::Error> {
+    let options = glib::VariantDict::new(None);
+    if let Some(token) = &open.activation_token {
+        options.insert("activation_token", token.as_str());
+    }
+
+    match &open.target {
+        OpenTarget::Uri(uri) => {
+            connection.call_sync(
+                Some(PORTAL_BUS_NAME),
+                PORTAL_OBJECT_PATH,
+                OPEN_URI_INTERFACE,
+                "OpenURI",
+                Some(&glib::Variant::tuple_from_iter([
+                    "".to_variant(),
+                    uri.to_variant(),
+                    options.end(),
+                ])),
+                None,
+                gio::DBusCallFlags::NONE,
+                -1,
+                gio::Cancellable::NONE,
+            )?;
+        }
+        OpenTarget::File(path) | OpenTarget::Reveal(path) => {
+            let file = fs::File::open(path).map_err(|why| {
+                glib::Error::new(
+                    gio::IOErrorEnum::Failed,
+                    &format!("Failed to open {:?}: {}", path, why),
+                )
+            })?;
+            let fd_list = gio::UnixFDList::new();
+            let handle = fd_list.append(file.as_raw_fd())?;
+
+            connection.call_with_unix_fd_list_sync(
+                Some(PORTAL_BUS_NAME),
+                PORTAL_OBJECT_PATH,
+                OPEN_URI_INTERFACE,
+                if matches!(open.target, OpenTarget::Reveal(_)) {
+                    "OpenDirectory"
+                } else {
+                    "OpenFile"
+                },
+                Some(&glib::Variant::tuple_from_iter([
+                    "".to_variant(),
+                    glib::variant::Handle(handle).to_variant(),
+                    options.end(),
+                ])),
+                None,
+                gio::DBusCallFlags::NONE,
+                -1,
+                Some(&fd_list),
+                gio::Cancellable::NONE,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn open_with_default_handler(open: &Open) {
+    let uri = match &open.target {
+        OpenTarget::Uri(uri) => uri.clone(),
+        OpenTarget::File(path) => gio::File::for_path(path).uri().into(),
+        OpenTarget::Reveal(path) => gio::File::for_path(path.parent().unwrap_or(path))
+            .uri()
+            .into(),
+    };
+
+    let context = gio::AppLaunchContext::new();
+    if let Some(token) = &open.activation_token {
+        context.setenv("XDG_ACTIVATION_TOKEN", token);
+    }
+
+    if let Err(why) = gio::AppInfo::launch_default_for_uri(&uri, Some(&context)) {
+        error!("Failed to open {}: {}", uri, why);
+    }
+}
+
+/// What the process waiting for an action of a notification reads from its stdin
+#[derive(Serialize, Deserialize)]
+struct NotificationWait {
+    notify: Notify,
+    /// The parts of the config launching the action depends on
+    terminal: Vec<String>,
+    launch_with_systemd_run: bool,
+}
+
+/// Sends the notification. Waiting for one of its actions is left to a new anyrun process, as
+/// D-Bus connections of GIO don't work in a forked child of this one.
+pub fn notify(notify: &Notify, config: &Config) {
+    if notify.actions.is_empty() {
+        let result = gio::bus_get_sync(gio::BusType::Session, gio::Cancellable::NONE)
+            .and_then(|connection| send_notification(&connection, notify, config));
+        if let Err(why) = result {
+            error!("Failed to send notification: {}", why);
+        }
+        return;
+    }
+
+    let wait = NotificationWait {
+        notify: notify.clone(),
+        terminal: config.terminal.clone(),
+        launch_with_systemd_run: config.launch_with_systemd_run,
+    };
+    let result = env::current_exe().and_then(|exe| {
+        let mut command = Command::new(exe);
+        command.arg("wait-for-notification");
+        spawn_waiter(command, &wait)
+    });
+    match result {
+        Ok(child) => info!(
+            "Spawned process {} to wait for notification actions",
+            child.id()
+        ),
+        Err(why) => error!(
+            "Failed to spawn a process for notification actions: {}",
+            why
+        ),
+    }
+}
+
+/// Spawns the waiting process in a new session and hands it what to wait for
+fn spawn_waiter(mut command: Command, wait: &NotificationWait) -> io::Result<Child> {
+    let wait = ron::to_string(wait).map_err(io::Error::other)?;
+
+    command.stdin(Stdio::piped()).stdout(Stdio::null());
+    new_session(&mut command);
+
+    let mut child = command.spawn()?;
+    // Dropping stdin closes it, so the waiter knows it has read everything
+    child
+        .stdin
+        .take()
+        .expect("Stdin of the waiter is piped")
+        .write_all(wait.as_bytes())?;
+    Ok(child)
+}
+
+/// Sends the notification read from stdin and waits in the foreground until one of its actions is
+/// invoked, it is closed or its timeout runs out, launching the invoked action
+pub fn wait_for_notification() -> Result<(), glib::Error> {
+    let mut input = String::new();
+    let wait = io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|why| why.to_string())
+        .and_then(|_| ron::from_str::<NotificationWait>(&input).map_err(|why| why.to_string()))
+        .map_err(|why| {
+            glib::Error::new(
+                gio::IOErrorEnum::InvalidData,
+                &format!("Failed to read the notification to wait for: {}", why),
+            )
+        })?;
+
+    let config = Config {
+        terminal: wait.terminal,
+        launch_with_systemd_run: wait.launch_with_systemd_run,
+        ..Config::default()
+    };
+    let connection = gio::bus_get_sync(gio::BusType::Session, gio::Cancellable::NONE)?;
+    send_notification(&connection, &wait.notify, &config)
+}
+
+/// Sends the notification and, if it has actions, runs a main loop until one is invoked, the
+/// notification is closed or its timeout runs out
+fn send_notification(
+    connection: &gio::DBusConnection,
+    notify: &Notify,
+    config: &Config,
+) -> Result<(), glib::Error> {
+    let main_loop = glib::MainLoop::new(None, false);
+    let notification_id = Rc::new(Cell::new(0u32));
+    let activation_token = Rc::new(RefCell::new(None));
+    let invoked_action = Rc::new(Cell::new(None));
+
+    // Subscribe before sending, so no signal for the notification can be missed
+    connection.signal_subscribe(
+        Some(NOTIFICATIONS_BUS_NAME),
+        Some(NOTIFICATIONS_INTERFACE),
+        None,
+        Some(NOTIFICATIONS_OBJECT_PATH),
+        None,
+        gio::DBusSignalFlags::NONE,
+        glib::clone!(@strong main_loop, @strong notification_id, @strong activation_token, @strong invoked_action => move |_, _, _, _, signal, parameters| {
+            match signal {
+                "ActivationToken" => {
+                    if let Some((id, token)) = parameters.get::<(u32, String)>() {
+                        if id == notification_id.get() {
+                            *activation_token.borrow_mut() = Some(token);
+                        }
+                    }
+                }
+                "ActionInvoked" => {
+                    if let Some((id, key)) = parameters.get::<(u32, String)>() {
+                        if id == notification_id.get() {
+                            invoked_action.set(key.parse::<usize>().ok());
+                            main_loop.quit();
+                        }
+                    }
+                }
+                "NotificationClosed" => {
+                    if let Some((id, _reason)) = parameters.get::<(u32, u32)>() {
+                        if id == notification_id.get() {
+                            main_loop.quit();
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }),
+    );
+
+    // The server expects pairs of action key and label
+    let actions: Vec<String> = notify
+        .actions
+        .iter()
+        .enumerate()
+        .flat_map(|(i, (label, _))| [i.to_string(), label.clone()])
+        .collect();
+
+    let icon = match &notify.icon {
+        Some(icon) if icon.starts_with('/') => gio::File::for_path(icon).uri().into(),
+        Some(icon) => icon.clone(),
+        None => String::new(),
+    };
+
+    let reply = connection.call_sync(
+        Some(NOTIFICATIONS_BUS_NAME),
+        NOTIFICATIONS_OBJECT_PATH,
+        NOTIFICATIONS_INTERFACE,
+        "Notify",
+        Some(&glib::Variant::tuple_from_iter([
+            "anyrun".to_variant(),
+            0u32.to_variant(),
+            icon.to_variant(),
+            notify.summary.to_variant(),
+            notify.body.to_variant(),
+            actions.to_variant(),
+            glib::VariantDict::new(None).end(),
+            notify.timeout.unwrap_or(-1).to_variant(),
+        ])),
+        None,
+        gio::DBusCallFlags::NONE,
+        -1,
+        gio::Cancellable::NONE,
+    )?;
+    notification_id.set(reply.child_value(0).get::<u32>().unwrap_or_default());
+
+    if notify.actions.is_empty() {
+        return Ok(());
+    }
+
+    let wait = notify
+        .timeout
+        .and_then(|timeout| u64::try_from(timeout).ok())
+        .filter(|timeout| *timeout > 0)
+        .map(Duration::from_millis)
+        .map_or(NOTIFICATION_WAIT_LIMIT, |timeout| {
+            timeout.min(NOTIFICATION_WAIT_LIMIT)
+        });
+    glib::timeout_add_local_once(
+        wait,
+        glib::clone!(@strong main_loop => move || {
+            info!("Stopped waiting for an action of the notification");
+            main_loop.quit();
+        }),
+    );
+
+    main_loop.run();
+
+    if let Some((_, action_launch)) = invoked_action
+        .get()
+        .and_then(|index| notify.actions.get(index))
+    {
+        let mut action_launch = action_launch.clone();
+        action_launch.activation_token = activation_token.take();
+        launch(&action_launch, config);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader},
+        sync::mpsc,
+        thread,
+    };
+
+    use super::*;
+
+    /// A private bus for stand-ins of the services, stopped when dropped
+    struct TestBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl TestBus {
+        fn start() -> Self {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("Failed to run dbus-daemon");
+
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take().unwrap())
+                .read_line(&mut address)
+                .expect("Failed to read the address of the test bus");
+
+            Self {
+                daemon,
+                address: address.trim().to_string(),
+            }
+        }
+    }
+
+    impl Drop for TestBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    fn connect(address: &str) -> gio::DBusConnection {
+        gio::DBusConnection::for_address_sync(
+            address,
+            gio::DBusConnectionFlags::AUTHENTICATION_CLIENT
+                | gio::DBusConnectionFlags::MESSAGE_BUS_CONNECTION,
+            None,
+            gio::Cancellable::NONE,
+        )
+        .expect("Failed to connect to the test bus")
+    }
+
+    /// A service to stand in for, with the introspection XML of its interface
+    struct StandIn {
+        bus_name: &'static str,
+        object_path: &'static str,
+        interface: &'static str,
+        introspection: &'static str,
+    }
+
+    type Reply = fn(&gio::DBusConnection, &str, &glib::Variant) -> Option<glib::Variant>;
+
+    /// Serves the stand-in on the bus from another thread until it was called `calls` times,
+    /// replying with `reply`. The method names and parameters of the calls are passed on.
+    fn serve(
+        bus: &TestBus,
+        stand_in: StandIn,
+        calls: usize,
+        reply: Reply,
+    ) -> mpsc::Receiver<(String, glib::Variant)> {
+        let address = bus.address.clone();
+        let (call_sender, call_receiver) = mpsc::channel();
+        let (ready_sender, ready_receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let context = glib::MainContext::new();
+            context
+                .with_thread_default(|| {
+                    let connection = connect(&address);
+                    let node = gio::DBusNodeInfo::for_xml(stand_in.introspection)
+                        .expect("Invalid introspection XML");
+                    let interface = node
+                        .lookup_interface(stand_in.interface)
+                        .expect("Interface missing from the introspection XML");
+
+                    let remaining = Rc::new(Cell::new(calls));
+                    connection
+                        .register_object(stand_in.object_path, &interface)
+                        .method_call(glib::clone!(@strong remaining => move |connection, _, _, _, method, parameters, invocation| {
+                            invocation.return_value(reply(&connection, method, &parameters).as_ref());
+                            call_sender.send((method.to_string(), parameters)).unwrap();
+                            remaining.set(remaining.get() - 1);
+                        }))
+                        .build()
+                        .expect("Failed to register the stand-in");
+
+                    connection
+                        .call_sync(
+                            Some("org.freedesktop.DBus"),
+                            "/org/freedesktop/DBus",
+                            "org.freedesktop.DBus",
+                            "RequestName",
+                            Some(&(stand_in.bus_name, 0u32).to_variant()),
+                            None,
+                            gio::DBusCallFlags::NONE,
+                            -1,
+                            gio::Cancellable::NONE,
+                        )
+                        .expect("Failed to own the name of the stand-in");
+                    ready_sender.send(()).unwrap();
+
+                    while remaining.get() > 0 {
+                        context.iteration(true);
+                    }
+                    // So that signals emitted in the last reply still get out
+                    let _ = connection.flush_sync(gio::Cancellable::NONE);
+                })
+                .expect("Failed to use the context of the stand-in");
+        });
+
+        ready_receiver.recv().expect("The stand-in failed to start");
+        call_receiver
+    }
+
+    const OPEN_URI_PORTAL: StandIn = StandIn {
+        bus_name: PORTAL_BUS_NAME,
+        object_path: PORTAL_OBJECT_PATH,
+        interface: OPEN_URI_INTERFACE,
+        introspection: r#"
+            <node>
+              <interface name="org.freedesktop.portal.OpenURI">
+                <method name="OpenURI">
+                  <arg type="s" name="parent_window" direction="in"/>
+                  <arg type="s" name="uri" direction="in"/>
+                  <arg type="a{sv}" name="options" direction="in"/>
+                  <arg type="o" name="handle" direction="out"/>
+                </method>
+                <method name="OpenFile">
+                  <arg type="s" name="parent_window" direction="in"/>
+                  <arg type="h" name="fd" direction="in"/>
+                  <arg type="a{sv}" name="options" direction="in"/>
+                  <arg type="o" name="handle" direction="out"/>
+                </method>
+                <method name="OpenDirectory">
+                  <arg type="s" name="parent_window" direction="in"/>
+                  <arg type="h" name="fd" direction="in"/>
+                  <arg type="a{sv}" name="options" direction="in"/>
+                  <arg type="o" name="handle" direction="out"/>
+                </method>
+              </interface>
+            </node>
+        "#,
+    };
+
+    fn portal_reply(_: &gio::DBusConnection, _: &str, _: &glib::Variant) -> Option<glib::Variant> {
+        let handle =
+            glib::variant::ObjectPath::try_from("/org/freedesktop/portal/desktop/request/1")
+                .unwrap();
+        Some((handle,).to_variant())
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn opens_through_the_portal() {
+        let bus = TestBus::start();
+        let calls = serve(&bus, OPEN_URI_PORTAL, 3, portal_reply);
+        let connection = connect(&bus.address);
+        let file = env::current_exe().unwrap();
+
+        let targets = [
+            OpenTarget::Uri("https://example.com/".to_string()),
+            OpenTarget::File(file.clone()),
+            OpenTarget::Reveal(file),
+        ];
+        for target in targets {
+            let open = Open {
+                target,
+                activation_token: Some("token".to_string()),
+            };
+            open_with_portal(&connection, &open).expect("Failed to call the portal");
+        }
+
+        let (method, parameters) = calls.recv().unwrap();
+        assert_eq!(method, "OpenURI");
+        assert_eq!(
+            parameters.child_value(1).get::<String>().as_deref(),
+            Some("https://example.com/")
+        );
+        let options = glib::VariantDict::new(Some(&parameters.child_value(2)));
+        assert_eq!(
+            options
+                .lookup::<String>("activation_token")
+                .unwrap()
+                .as_deref(),
+            Some("token")
+        );
+
+        for expected in ["OpenFile", "OpenDirectory"] {
+            let (method, parameters) = calls.recv().unwrap();
+            assert_eq!(method, expected);
+            assert!(parameters.child_value(1).is::<glib::variant::Handle>());
+        }
+    }
+
+    const NOTIFICATION_SERVER: StandIn = StandIn {
+        bus_name: NOTIFICATIONS_BUS_NAME,
+        object_path: NOTIFICATIONS_OBJECT_PATH,
+        interface: NOTIFICATIONS_INTERFACE,
+        introspection: r#"
+            <node>
+              <interface name="org.freedesktop.Notifications">
+                <method name="Notify">
+                  <arg type="s" name="app_name" direction="in"/>
+                  <arg type="u" name="replaces_id" direction="in"/>
+                  <arg type="s" name="app_icon" direction="in"/>
+                  <arg type="s" name="summary" direction="in"/>
+                  <arg type="s" name="body" direction="in"/>
+                  <arg type="as" name="actions" direction="in"/>
+                  <arg type="a{sv}" name="hints" direction="in"/>
+                  <arg type="i" name="expire_timeout" direction="in"/>
+                  <arg type="u" name="id" direction="out"/>
+                </method>
+              </interface>
+            </node>
+        "#,
+    };
+
+    const NOTIFICATION_ID: u32 = 7;
+
+    /// Clicks the first action right away, for notifications with a summary of "click"
+    fn notification_reply(
+        connection: &gio::DBusConnection,
+        _: &str,
+        parameters: &glib::Variant,
+    ) -> Option<glib::Variant> {
+        if parameters.child_value(3).get::<String>().as_deref() == Some("click") {
+            for (signal, parameters) in [
+                ("ActivationToken", (NOTIFICATION_ID, "token").to_variant()),
+                ("ActionInvoked", (NOTIFICATION_ID, "0").to_variant()),
+            ] {
+                connection
+                    .emit_signal(
+                        None,
+                        NOTIFICATIONS_OBJECT_PATH,
+                        NOTIFICATIONS_INTERFACE,
+                        signal,
+                        Some(&parameters),
+                    )
+                    .unwrap();
+            }
+        }
+        Some((NOTIFICATION_ID,).to_variant())
+    }
+
+    /// Spawns this test binary to run `notification_waiter` in place of the subcommand, and waits
+    /// for it to exit
+    fn wait_in_new_process(bus: &TestBus, notify: Notify) {
+        let mut command = Command::new(env::current_exe().unwrap());
+        command
+            .args([
+                "--exact",
+                "post_run::tests::notification_waiter",
+                "--ignored",
+            ])
+            .env("DBUS_SESSION_BUS_ADDRESS", &bus.address)
+            .env("ANYRUN_TEST_NOTIFICATION_WAITER", "1");
+        let wait = NotificationWait {
+            notify,
+            terminal: Vec::new(),
+            launch_with_systemd_run: false,
+        };
+        let mut child = spawn_waiter(command, &wait).expect("Failed to spawn the waiter");
+
+        for _ in 0..50 {
+            if let Some(status) = child.try_wait().unwrap() {
+                assert!(status.success(), "The waiter failed");
+                return;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        let _ = child.kill();
+        panic!("The waiter didn't stop waiting");
+    }
+
+    /// Stands in for the `wait-for-notification` subcommand in the processes spawned by
+    /// `notification_actions`
+    #[test]
+    #[ignore = "run by notification_actions"]
+    fn notification_waiter() {
+        if env::var_os("ANYRUN_TEST_NOTIFICATION_WAITER").is_some() {
+            wait_for_notification().expect("Failed to wait for the notification");
+        }
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn notification_actions() {
+        let bus = TestBus::start();
+        let calls = serve(&bus, NOTIFICATION_SERVER, 2, notification_reply);
+        let marker = env::temp_dir().join(format!("anyrun-notify-test-{}", std::process::id()));
+        let notification = |summary: &str| Notify {
+            summary: summary.to_string(),
+            body: "Body".to_string(),
+            icon: None,
+            actions: vec![(
+                "Open".to_string(),
+                Launch {
+                    argv: vec!["touch".to_string(), marker.to_string_lossy().into()],
+                    working_dir: None,
+                    env: Vec::new(),
+                    terminal: false,
+                    activation_token: None,
+                },
+            )],
+            timeout: Some(200),
+        };
+
+        // The invoked action is launched
+        wait_in_new_process(&bus, notification("click"));
+        let (_, parameters) = calls.recv().unwrap();
+        assert_eq!(
+            parameters.child_value(5).get::<Vec<String>>(),
+            Some(vec!["0".to_string(), "Open".to_string()])
+        );
+        for _ in 0..50 {
+            if marker.exists() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        assert!(marker.exists(), "The action wasn't launched");
+        let _ = fs::remove_file(&marker);
+
+        // Waiting stops with the timeout of the notification if the server never closes it
+        wait_in_new_process(&bus, notification("ignore"));
+        calls.recv().unwrap();
+        assert!(!marker.exists());
+    }
+}
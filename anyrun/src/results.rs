@@ -0,0 +1,322 @@
+use std::{cell::RefCell, rc::Rc};
+
+use gtk::{
+    gdk,
+    glib::{self, clone},
+    prelude::*,
+};
+
+use crate::{
+    config::{style_names, Layout, RuntimeData},
+    plugins::{
+        bind_bar_cell, bind_grid_cell, build_match_box, match_classes, setup_bar_cell,
+        setup_grid_cell,
+    },
+    types::GMatch,
+};
+
+const LIST_PAGE: &str = "list";
+const GRID_PAGE: &str = "grid";
+const BAR_PAGE: &str = "bar";
+
+/// The views the matches can be laid out in, of which the one for the current layout is shown.
+/// They share a single selection, so switching between them keeps the selected match.
+#[derive(Clone)]
+pub struct Results {
+    pub list: gtk::ListBox,
+    /// Shown instead of `list` for rows from the user's template
+    pub template_list: Option<gtk::ListView>,
+    pub grid: gtk::GridView,
+    pub bar: gtk::ListView,
+    pub stack: gtk::Stack,
+    selection: gtk::SingleSelection,
+    /// The matches the list has rows for, all of them in the list layout and none otherwise, as
+    /// the list builds the rows of all its matches at once
+    list_matches: gtk::SliceListModel,
+}
+
+impl Results {
+    pub fn new(runtime_data: Rc<RefCell<RuntimeData>>) -> Self {
+        let list_store = runtime_data.borrow().list_store.clone();
+
+        let list = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::Browse)
+            .name(style_names::MAIN)
+            .build();
+
+        let list_matches = gtk::SliceListModel::new(Some(list_store.clone()), 0, 0);
+        list.bind_model(
+            Some(&list_matches),
+            clone!(@strong runtime_data => move |match_row| {
+                let gmatch = match_row
+                    .clone()
+                    .downcast::<GMatch>()
+                    .expect("Can't downcast glib::Object to GMatch");
+                let classes = match_classes(&runtime_data.borrow(), &gmatch);
+
+                let row = gtk::ListBoxRow::builder()
+                    .child(&build_match_box(runtime_data.clone(), gmatch))
+                    .build();
+                // Added to the classes of the row, like `activatable`, rather than replacing them
+                for class in classes {
+                    row.add_css_class(&class);
+                }
+                row.upcast()
+            }),
+        );
+
+        // Connected after the list has its rows, so the list can follow the selection
+        let selection = gtk::SingleSelection::new(Some(list_store));
+
+        let grid = gtk::GridView::builder()
+            .model(&selection)
+            .factory(&cell_factory(
+                runtime_data.clone(),
+                setup_grid_cell,
+                bind_grid_cell,
+            ))
+            .single_click_activate(true)
+            .name(style_names::GRID)
+            .build();
+
+        let bar = gtk::ListView::builder()
+            .model(&selection)
+            .factory(&cell_factory(
+                runtime_data.clone(),
+                setup_bar_cell,
+                bind_bar_cell,
+            ))
+            .orientation(gtk::Orientation::Horizontal)
+            .single_click_activate(true)
+            .name(style_names::BAR)
+            .build();
+
+        let template_list = runtime_data
+            .borrow()
+            .match_template
+            .as_ref()
+            .map(|template| template_view(&selection, template));
+
+        selection.connect_selected_notify(clone!(@weak list, @weak bar => move |selection| {
+            let row = list.row_at_index(selection.selected() as i32);
+            if list.selected_row() != row {
+                list.select_row(row.as_ref());
+            }
+
+            // The focus stays in the entry in the bar layout, so the bar has to be scrolled along
+            if let Some(index) = selected_index(selection) {
+                let _ = bar.activate_action("list.scroll-to-item", Some(&(index as u32).to_variant()));
+            }
+        }));
+        list.connect_row_selected(clone!(@weak selection => move |_, row| {
+            if let Some(row) = row {
+                selection.set_selected(row.index() as u32);
+            }
+        }));
+
+        // The window is fitted to the height of the matches with `max_height` set
+        let fit_height = runtime_data.borrow().config.max_height.is_some();
+
+        let stack = gtk::Stack::builder().vhomogeneous(false).build();
+        let list_page = match &template_list {
+            Some(template_list) => scrolled(template_list, fit_height),
+            None => scrolled(&list, fit_height),
+        };
+        stack.add_named(&list_page, Some(LIST_PAGE));
+        stack.add_named(&scrolled(&grid, fit_height), Some(GRID_PAGE));
+        stack.add_named(
+            &gtk::ScrolledWindow::builder()
+                .hexpand(true)
+                .focusable(false)
+                .vscrollbar_policy(gtk::PolicyType::Never)
+                .child(&bar)
+                .build(),
+            Some(BAR_PAGE),
+        );
+
+        let results = Self {
+            list,
+            template_list,
+            grid,
+            bar,
+            stack,
+            selection,
+            list_matches,
+        };
+        results.set_layout(runtime_data.borrow().config.layout);
+        results
+    }
+
+    pub fn set_layout(&self, layout: Layout) {
+        let list_size = if layout == Layout::List && self.template_list.is_none() {
+            u32::MAX
+        } else {
+            0
+        };
+        if self.list_matches.size() != list_size {
+            self.list_matches.set_size(list_size);
+            let row = self.list.row_at_index(self.selection.selected() as i32);
+            self.list.select_row(row.as_ref());
+        }
+
+        self.stack.set_visible_child_name(match layout {
+            Layout::List => LIST_PAGE,
+            Layout::Grid => GRID_PAGE,
+            Layout::Bar => BAR_PAGE,
+        });
+    }
+
+    /// The index of the selected match
+    pub fn selected(&self) -> Option<usize> {
+        selected_index(&self.selection)
+    }
+
+    pub fn select(&self, index: u32) {
+        self.selection.set_selected(index);
+    }
+
+    /// Moves the selection by `offset` matches, returns `false` if there is no match there
+    pub fn move_selection(&self, offset: i32) -> bool {
+        let Some(index) = self
+            .selected()
+            .and_then(|selected| selected.checked_add_signed(offset as isize))
+            .filter(|index| *index < self.selection.n_items() as usize)
+        else {
+            return false;
+        };
+
+        self.select(index as u32);
+        true
+    }
+
+    /// Calls `f` with the index of the newly selected match, in either view
+    pub fn connect_selection_changed<F: Fn(Option<usize>) + 'static>(&self, f: F) {
+        self.selection
+            .connect_selected_notify(move |selection| f(selected_index(selection)));
+    }
+}
+
+fn selected_index(selection: &gtk::SingleSelection) -> Option<usize> {
+    match selection.selected() {
+        gtk::INVALID_LIST_POSITION => None,
+        index => Some(index as usize),
+    }
+}
+
+/// A factory building the cells of the matches in a list view with `setup_cell`, and showing the
+/// matches in them with `bind_cell`
+fn cell_factory(
+    runtime_data: Rc<RefCell<RuntimeData>>,
+    setup_cell: fn() -> gtk::Box,
+    bind_cell: fn(&gtk::Box, &RuntimeData, &GMatch),
+) -> gtk::SignalListItemFactory {
+    let factory = gtk::SignalListItemFactory::new();
+    factory.connect_setup(move |_, list_item| {
+        let list_item = list_item
+            .downcast_ref::<gtk::ListItem>()
+            .expect("Can't downcast glib::Object to ListItem");
+
+        let cell = setup_cell();
+
+        // The views only activate their items with the primary button
+        let middle_click = gtk::GestureClick::builder()
+            .button(gdk::BUTTON_MIDDLE)
+            .build();
+        middle_click.connect_released(clone!(@weak list_item, @weak cell => move |_, _, _, _| {
+            let _ = cell.activate_action(
+                "list.activate-item",
+                Some(&list_item.position().to_variant()),
+            );
+        }));
+        cell.add_controller(middle_click);
+
+        list_item.set_child(Some(&cell));
+    });
+    factory.connect_bind(move |_, list_item| {
+        let list_item = list_item
+            .downcast_ref::<gtk::ListItem>()
+            .expect("Can't downcast glib::Object to ListItem");
+        let gmatch = list_item
+            .item()
+            .and_downcast::<GMatch>()
+            .expect("Can't downcast glib::Object to GMatch");
+        let cell = list_item
+            .child()
+            .and_downcast::<gtk::Box>()
+            .expect("Can't downcast gtk::Widget to Box");
+
+        bind_cell(&cell, &runtime_data.borrow(), &gmatch);
+    });
+    factory
+}
+
+/// A list of rows from the user's template, which the factory compiles once for all of them,
+/// rather than building each row of a list box from the template
+fn template_view(selection: &gtk::SingleSelection, template: &glib::Bytes) -> gtk::ListView {
+    let view = gtk::ListView::builder()
+        .model(selection)
+        .factory(&gtk::BuilderListItemFactory::from_bytes(
+            gtk::BuilderScope::NONE,
+            template,
+        ))
+        .single_click_activate(true)
+        .name(style_names::MAIN)
+        .build();
+
+    // The rows only activate their items with the primary button, so a middle click activates
+    // the row under it like `Enter` would
+    let middle_click = gtk::GestureClick::builder()
+        .button(gdk::BUTTON_MIDDLE)
+        .build();
+    middle_click.connect_released(clone!(@weak view => move |_, _, x, y| {
+        let mut widget = view.pick(x, y, gtk::PickFlags::DEFAULT);
+        while let Some(child) = widget {
+            if child.parent().as_ref() == Some(view.upcast_ref::<gtk::Widget>()) {
+                child.activate();
+                break;
+            }
+            widget = child.parent();
+        }
+    }));
+    view.add_controller(middle_click);
+
+    view
+}
+
+fn scrolled(view: &impl IsA<gtk::Widget>, propagate_height: bool) -> gtk::ScrolledWindow {
+    gtk::ScrolledWindow::builder()
+        .vexpand(true)
+        .hexpand(true)
+        .focusable(false)
+        .propagate_natural_height(propagate_height)
+        .child(view)
+        .build()
+}
+
+/// The layout for the matches of the plugins in use: the grid if all of them are listed in
+/// `grid_plugins`, the configured layout otherwise
+pub fn layout_in_use(runtime_data: &RuntimeData) -> Layout {
+    let config = &runtime_data.config;
+    // The window is shaped for the bar, so it's kept for all plugins
+    if config.grid_plugins.is_empty() || config.layout == Layout::Bar {
+        return config.layout;
+    }
+
+    let in_grid = |index: usize| {
+        config
+            .plugins
+            .get(index)
+            .is_some_and(|path| config.grid_plugins.contains(path))
+    };
+
+    let all_in_grid = match runtime_data.exclusive {
+        Some(plugin_id) => in_grid(plugin_id),
+        None => (0..runtime_data.plugins.len()).all(in_grid),
+    };
+
+    if all_in_grid {
+        Layout::Grid
+    } else {
+        config.layout
+    }
+}
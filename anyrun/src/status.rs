@@ -0,0 +1,133 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Duration,
+};
+
+use anyrun_interface::PluginRef as Plugin;
+use gtk::prelude::*;
+
+use crate::config::style_names;
+
+/// Added to the entries of the plugins that are still looking for matches
+const PENDING_CLASS: &str = "pending";
+
+/// A footer showing for each plugin in use whether it's still looking for matches, and otherwise
+/// how many it found and how long that took
+#[derive(Clone)]
+pub struct StatusBar {
+    pub container: gtk::Box,
+    entries: Rc<RefCell<Vec<StatusEntry>>>,
+    /// Counts the inputs the matches were requested for, so that late replies for an earlier
+    /// input don't show up
+    generation: Rc<Cell<u64>>,
+}
+
+struct StatusEntry {
+    container: gtk::Box,
+    spinner: gtk::Spinner,
+    label: gtk::Label,
+    name: String,
+}
+
+impl StatusBar {
+    pub fn new() -> Self {
+        let container = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(12)
+            .name(style_names::STATUS)
+            .build();
+
+        Self {
+            container,
+            entries: Rc::new(RefCell::new(Vec::new())),
+            generation: Rc::new(Cell::new(0)),
+        }
+    }
+
+    /// Shows all of the plugins as pending, returns the generation to pass to [`Self::finish`]
+    pub fn start(&self, plugins: &[Plugin]) -> u64 {
+        let mut entries = self.entries.borrow_mut();
+        for entry in entries.drain(..) {
+            self.container.remove(&entry.container);
+        }
+
+        for plugin in plugins {
+            let entry = StatusEntry::new(plugin.info()().name.to_string());
+            self.container.append(&entry.container);
+            entries.push(entry);
+        }
+
+        self.generation.set(self.generation.get() + 1);
+        self.generation.get()
+    }
+
+    /// Shows the result of the plugin at `index` of the ones passed to [`Self::start`]
+    pub fn finish(&self, generation: u64, index: usize, count: usize, elapsed: Duration) {
+        self.with_entry(generation, index, |entry| entry.finish(count, elapsed));
+    }
+
+    /// Shows that the plugin at `index` of the ones passed to [`Self::start`] gave up on the input
+    pub fn cancel(&self, generation: u64, index: usize) {
+        self.with_entry(generation, index, StatusEntry::cancel);
+    }
+
+    fn with_entry(&self, generation: u64, index: usize, f: impl FnOnce(&StatusEntry)) {
+        if self.generation.get() != generation {
+            return;
+        }
+
+        if let Some(entry) = self.entries.borrow().get(index) {
+            f(entry);
+        }
+    }
+}
+
+impl StatusEntry {
+    fn new(name: String) -> Self {
+        let container = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(4)
+            .name(style_names::STATUS)
+            .css_classes([PENDING_CLASS])
+            .build();
+        let spinner = gtk::Spinner::builder()
+            .spinning(true)
+            .name(style_names::STATUS)
+            .build();
+        let label = gtk::Label::builder()
+            .label(&name)
+            .name(style_names::STATUS)
+            .build();
+
+        container.append(&spinner);
+        container.append(&label);
+
+        Self {
+            container,
+            spinner,
+            label,
+            name,
+        }
+    }
+
+    fn finish(&self, count: usize, elapsed: Duration) {
+        self.done(&format!(
+            "{}: {} in {} ms",
+            self.name,
+            count,
+            elapsed.as_millis()
+        ));
+    }
+
+    fn cancel(&self) {
+        self.done(&format!("{}: cancelled", self.name));
+    }
+
+    fn done(&self, text: &str) {
+        self.spinner.set_spinning(false);
+        self.spinner.set_visible(false);
+        self.container.remove_css_class(PENDING_CLASS);
+        self.label.set_text(text);
+    }
+}
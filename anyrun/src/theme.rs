@@ -0,0 +1,155 @@
+use std::{cell::Cell, rc::Rc};
+
+use gtk::{gdk, gio, glib, prelude::*};
+use log::*;
+
+use crate::config::Theme;
+
+const BASE_CSS: &str = include_str!("../res/themes/base.css");
+const LIGHT_COLORS: &str = include_str!("../res/themes/light.css");
+const DARK_COLORS: &str = include_str!("../res/themes/dark.css");
+
+const PORTAL_BUS_NAME: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_OBJECT_PATH: &str = "/org/freedesktop/portal/desktop";
+const SETTINGS_INTERFACE: &str = "org.freedesktop.portal.Settings";
+const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+const COLOR_SCHEME_KEY: &str = "color-scheme";
+
+/// Loads the built-in theme below the user's `style.css`. With [`Theme::Auto`] it follows the
+/// color scheme preference of the settings portal, also when it changes while anyrun is open.
+pub fn load_theme(display: &gdk::Display, theme: Theme) {
+    let provider = gtk::CssProvider::new();
+
+    match theme {
+        Theme::Gtk => return,
+        Theme::Light => apply_theme(&provider, false),
+        Theme::Dark => apply_theme(&provider, true),
+        Theme::Auto => follow_color_scheme(&provider),
+    }
+
+    gtk::style_context_add_provider_for_display(
+        display,
+        &provider,
+        gtk::STYLE_PROVIDER_PRIORITY_APPLICATION - 1,
+    );
+}
+
+fn apply_theme(provider: &gtk::CssProvider, dark: bool) {
+    let colors = if dark { DARK_COLORS } else { LIGHT_COLORS };
+    provider.load_from_data(&format!("{}\n{}", colors, BASE_CSS));
+
+    // So that the widgets of the GTK theme match
+    if let Some(settings) = gtk::Settings::default() {
+        settings.set_gtk_application_prefer_dark_theme(dark);
+    }
+}
+
+/// Starts out light, and switches to the color scheme from the portal once it has replied
+fn follow_color_scheme(provider: &gtk::CssProvider) {
+    apply_theme(provider, false);
+
+    glib::MainContext::default().spawn_local(glib::clone!(@strong provider => async move {
+        let connection = match gio::bus_get_future(gio::BusType::Session).await {
+            Ok(connection) => connection,
+            Err(why) => {
+                warn!(
+                    "Failed to connect to the session bus for the color scheme: {}",
+                    why
+                );
+                return;
+            }
+        };
+
+        // A change arriving before the reply is newer than what the reply says
+        let changed = Rc::new(Cell::new(false));
+        connection.signal_subscribe(
+            Some(PORTAL_BUS_NAME),
+            Some(SETTINGS_INTERFACE),
+            Some("SettingChanged"),
+            Some(PORTAL_OBJECT_PATH),
+            Some(APPEARANCE_NAMESPACE),
+            gio::DBusSignalFlags::NONE,
+            glib::clone!(@strong provider, @strong changed => move |_, _, _, _, _, parameters| {
+                if let Some((namespace, key, value)) =
+                    parameters.get::<(String, String, glib::Variant)>()
+                {
+                    if namespace == APPEARANCE_NAMESPACE && key == COLOR_SCHEME_KEY {
+                        changed.set(true);
+                        apply_theme(&provider, is_dark(&value));
+                    }
+                }
+            }),
+        );
+
+        match read_color_scheme(&connection).await {
+            Ok(value) if !changed.get() => apply_theme(&provider, is_dark(&value)),
+            Ok(_) => (),
+            Err(why) => warn!(
+                "Failed to read the color scheme from the settings portal: {}",
+                why
+            ),
+        }
+    }));
+}
+
+async fn read_color_scheme(connection: &gio::DBusConnection) -> Result<glib::Variant, glib::Error> {
+    let call = |method: &str| {
+        connection.call_future(
+            Some(PORTAL_BUS_NAME),
+            PORTAL_OBJECT_PATH,
+            SETTINGS_INTERFACE,
+            method,
+            Some(&(APPEARANCE_NAMESPACE, COLOR_SCHEME_KEY).to_variant()),
+            None,
+            gio::DBusCallFlags::NONE,
+            -1,
+        )
+    };
+
+    // `ReadOne` is only in newer versions of the portal
+    let reply = match call("ReadOne").await {
+        Ok(reply) => reply,
+        Err(_) => call("Read").await?,
+    };
+    Ok(reply.child_value(0))
+}
+
+/// The color scheme is 1 for dark, 2 for light and 0 for no preference. `Read` wraps it in
+/// another variant.
+fn is_dark(value: &glib::Variant) -> bool {
+    let mut value = value.clone();
+    while let Some(inner) = value.as_variant() {
+        value = inner;
+    }
+    value.get::<u32>() == Some(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_dark_preference_is_dark() {
+        assert!(is_dark(&1u32.to_variant()));
+        assert!(!is_dark(&0u32.to_variant()));
+        assert!(!is_dark(&2u32.to_variant()));
+    }
+
+    #[test]
+    fn the_preference_is_unwrapped_from_variants() {
+        // As `ReadOne` replies
+        let read_one = glib::Variant::from_variant(&1u32.to_variant());
+        assert!(is_dark(&read_one));
+
+        // As `Read` replies
+        let read = glib::Variant::from_variant(&read_one);
+        assert!(is_dark(&read));
+    }
+
+    #[test]
+    fn other_values_are_not_dark() {
+        assert!(!is_dark(&"dark".to_variant()));
+        assert!(!is_dark(&1i32.to_variant()));
+        assert!(!is_dark(&glib::Variant::from_variant(&true.to_variant())));
+    }
+}
@@ -22,7 +22,11 @@ mod imp {
         // workarond to get something like `Option<u64>` for id with glib because I couldn't find some
         id_some: Cell<bool>,
         pub plugin_id: Cell<u64>,
+        pub plugin_name: RefCell<String>,
+        pub plugin_icon: RefCell<String>,
         pub first: Cell<bool>,
+        pub title_markup: RefCell<String>,
+        pub description_markup: RefCell<Option<String>>,
     }
 
     #[glib::object_subclass]
@@ -45,7 +49,11 @@ mod imp {
                     glib::ParamSpecUInt64::builder("id").build(),
                     glib::ParamSpecBoolean::builder("id-some").build(),
                     glib::ParamSpecUInt64::builder("plugin-id").build(),
+                    glib::ParamSpecString::builder("plugin-name").build(),
+                    glib::ParamSpecString::builder("plugin-icon").build(),
                     glib::ParamSpecBoolean::builder("first").build(),
+                    glib::ParamSpecString::builder("title-markup").build(),
+                    glib::ParamSpecString::builder("description-markup").build(),
                 ]
             })
         }
@@ -94,12 +102,36 @@ mod imp {
                         .expect("type conformity checked by `Object::set_property`");
                     self.plugin_id.replace(plugin_id);
                 }
+                "plugin-name" => {
+                    let plugin_name = value
+                        .get()
+                        .expect("type conformity checked by `Object::set_property`");
+                    self.plugin_name.replace(plugin_name);
+                }
+                "plugin-icon" => {
+                    let plugin_icon = value
+                        .get()
+                        .expect("type conformity checked by `Object::set_property`");
+                    self.plugin_icon.replace(plugin_icon);
+                }
                 "first" => {
                     let first = value
                         .get()
                         .expect("type conformity checked by `Object::set_property`");
                     self.first.replace(first);
                 }
+                "title-markup" => {
+                    let title_markup = value
+                        .get()
+                        .expect("type conformity checked by `Object::set_property`");
+                    self.title_markup.replace(title_markup);
+                }
+                "description-markup" => {
+                    let description_markup = value
+                        .get()
+                        .expect("type conformity checked by `Object::set_property`");
+                    self.description_markup.replace(description_markup);
+                }
                 _ => unimplemented!(),
             }
         }
@@ -113,7 +145,11 @@ mod imp {
                 "id" => self.id.get().to_value(),
                 "id-some" => self.id_some.get().to_value(),
                 "plugin-id" => self.plugin_id.get().to_value(),
+                "plugin-name" => self.plugin_name.borrow().to_value(),
+                "plugin-icon" => self.plugin_icon.borrow().to_value(),
                 "first" => self.first.get().to_value(),
+                "title-markup" => self.title_markup.borrow().to_value(),
+                "description-markup" => self.description_markup.borrow().to_value(),
                 _ => unimplemented!(),
             }
         }
@@ -193,6 +229,22 @@ impl GMatch {
         self.set_property("plugin-id", value)
     }
 
+    pub fn get_plugin_name(&self) -> String {
+        self.property("plugin-name")
+    }
+
+    pub fn set_plugin_name(&self, value: String) {
+        self.set_property("plugin-name", value)
+    }
+
+    pub fn get_plugin_icon(&self) -> String {
+        self.property("plugin-icon")
+    }
+
+    pub fn set_plugin_icon(&self, value: String) {
+        self.set_property("plugin-icon", value)
+    }
+
     pub fn get_first(&self) -> bool {
         self.property("first")
     }
@@ -200,6 +252,22 @@ impl GMatch {
     pub fn set_first(&self, value: bool) {
         self.set_property("first", value);
     }
+
+    pub fn get_title_markup(&self) -> String {
+        self.property("title-markup")
+    }
+
+    pub fn set_title_markup(&self, value: String) {
+        self.set_property("title-markup", value)
+    }
+
+    pub fn get_description_markup(&self) -> Option<String> {
+        self.property("description-markup")
+    }
+
+    pub fn set_description_markup(&self, value: Option<String>) {
+        self.set_property("description-markup", value)
+    }
 }
 
 impl Default for GMatch {
@@ -217,6 +285,8 @@ impl std::fmt::Display for GMatch {
             .field("icon", &self.get_icon())
             .field("id", &self.get_id())
             .field("plugin_id", &self.get_plugin_id())
+            .field("plugin_name", &self.get_plugin_name())
+            .field("plugin_icon", &self.get_plugin_icon())
             .field("first", &self.get_first())
             .finish()
     }
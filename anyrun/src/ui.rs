@@ -1,19 +1,50 @@
-use std::{cell::RefCell, fs, io, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    fs, io,
+    rc::Rc,
+    time::Duration,
+};
 
-use anyrun_interface::{HandleResult, Match};
+use anyrun_interface::{
+    Activation, ClipboardType, EntryText, FormField, FormFieldKind, FormValue, HandleResult,
+    LaunchCommand, Match, Modifiers, PluginRef as Plugin, Preview,
+};
 use gtk::{
     gdk::{self, Key},
-    glib,
+    gdk_pixbuf::Pixbuf,
+    gio, glib,
     prelude::*,
 };
 use gtk_layer_shell::LayerShell;
 use log::*;
 
 use crate::{
-    config::{style_names, Edge, PostRunAction, RelativeNum, RuntimeData},
+    config::{
+        style_names, ClipboardCopy, Config, Edge, Launch, Layout, NavLevel, Notify, Open,
+        OpenTarget, PendingConfirmation, PendingForm, Position, PostRunAction, PreviewPosition,
+        RelativeNum, RuntimeData, Selection,
+    },
+    highlight,
+    icons::build_image,
+    plugins::{is_valid_markup, refresh_matches},
+    results::Results,
+    theme,
     types::GMatch,
 };
 
+/// The name of the page with the matches in the stack they share with the form view
+const MATCHES_PAGE: &str = "matches";
+
+/// How much of a file is read for its preview
+const PREVIEW_FILE_LIMIT: usize = 64 * 1024;
+
+// Widgets for plugins to ask the user for more input with
+#[derive(Clone)]
+pub struct Prompts {
+    pub confirm_bar: Rc<gtk::Box>,
+    pub form_view: Rc<gtk::Box>,
+}
+
 pub fn setup_main_window(
     app: &impl IsA<gtk::Application>,
     runtime_data: Rc<RefCell<RuntimeData>>,
@@ -21,34 +52,33 @@ pub fn setup_main_window(
     let window = gtk::ApplicationWindow::builder()
         .application(app)
         .name(style_names::WINDOW)
-        .default_height(500) // TODO: move to config. Yes, let window be static size
         .build();
 
-    setup_layer_shell(&window, runtime_data.clone());
+    let default_height = default_height(&runtime_data.borrow().config);
+    if let Some(height) = default_height {
+        window.set_default_size(-1, height);
+    }
+
+    if runtime_data.borrow().layer_shell {
+        setup_layer_shell(&window, runtime_data.clone());
+    } else {
+        setup_toplevel(&window);
+    }
     window.present();
     window
 }
 
+/// Sets up a regular window for compositors without layer-shell. Unlike layer-shell surfaces it
+/// can't be placed or kept above other windows, which is left to the compositor.
+fn setup_toplevel(window: &impl GtkWindowExt) {
+    window.set_title(Some("Anyrun"));
+    window.set_decorated(false);
+}
+
 fn setup_layer_shell(window: &impl GtkWindowExt, runtime_data: Rc<RefCell<RuntimeData>>) {
     window.init_layer_shell();
 
     let config = &runtime_data.borrow().config;
-    let geometry = runtime_data.borrow().geometry;
-    let width = geometry.width().try_into().unwrap();
-    let height = geometry.height().try_into().unwrap();
-
-    for (i, edge) in config.edges.clone().into_iter().enumerate() {
-        let margin = config
-            .margin
-            .get(i)
-            .unwrap_or(&RelativeNum::default())
-            .to_val(match edge {
-                Edge::Left | Edge::Right => width,
-                Edge::Top | Edge::Bottom => height,
-            });
-        window.set_anchor(edge.into(), true);
-        window.set_margin(edge.into(), margin);
-    }
 
     window.set_namespace("anyrun");
 
@@ -65,22 +95,260 @@ fn setup_layer_shell(window: &impl GtkWindowExt, runtime_data: Rc<RefCell<Runtim
     window.set_layer(config.layer.into());
 }
 
+/// Places the window where `position` says on the monitor the compositor shows it on, which is
+/// the focused one, and again whenever it's moved to another monitor. If the window covers the
+/// monitor its content is placed inside it instead.
+pub fn setup_placement(window: Rc<gtk::ApplicationWindow>, runtime_data: Rc<RefCell<RuntimeData>>) {
+    let content = window.child().expect("Window has no content to place");
+
+    place_window(&window, &content, &runtime_data.borrow());
+
+    if let Some(surface) = window.surface() {
+        surface.connect_enter_monitor(
+            glib::clone!(@weak window, @weak content, @weak runtime_data => move |_, monitor| {
+                runtime_data.borrow_mut().geometry = monitor.geometry();
+                place_window(&window, &content, &runtime_data.borrow());
+            }),
+        );
+    }
+
+    if matches!(runtime_data.borrow().config.position, Position::Pointer) {
+        let motion = gtk::EventControllerMotion::new();
+        motion.connect_enter(
+            glib::clone!(@weak window, @weak content => move |motion, x, y| {
+                // Kept inside the window when the pointer is close to the right or bottom edge
+                let x = (x as i32).min(window.width() - content.width()).max(0);
+                let y = (y as i32).min(window.height() - content.height()).max(0);
+                anchor_content(&content, &[(Edge::Left, x), (Edge::Top, y)]);
+
+                // Only the first position is used, so the content doesn't follow the pointer
+                window.remove_controller(motion);
+            }),
+        );
+        window.add_controller(motion);
+    }
+}
+
+/// Closes the window on clicks outside its content and when it loses the keyboard focus, if
+/// the config asks for it
+pub fn setup_close_triggers(
+    window: Rc<gtk::ApplicationWindow>,
+    runtime_data: Rc<RefCell<RuntimeData>>,
+) {
+    let config = &runtime_data.borrow().config;
+
+    if config.close_on_click_outside {
+        let content = window.child().expect("Window has no content");
+
+        let gesture_click = gtk::GestureClick::builder().button(0).build();
+        gesture_click.connect_pressed(
+            glib::clone!(@weak window, @weak content => move |_, _, x, y| {
+                let inside = window
+                    .pick(x, y, gtk::PickFlags::DEFAULT)
+                    .is_some_and(|widget| widget == content || widget.is_ancestor(&content));
+                if !inside {
+                    window.close();
+                }
+            }),
+        );
+        window.add_controller(gesture_click);
+    }
+
+    // With exclusive keyboard focus the window can't lose it
+    if config.close_on_focus_loss && !config.steal_focus {
+        window.connect_is_active_notify(|window| {
+            if !window.is_active() {
+                window.close();
+            }
+        });
+    }
+}
+
+fn place_window(
+    window: &gtk::ApplicationWindow,
+    content: &gtk::Widget,
+    runtime_data: &RuntimeData,
+) {
+    let config = &runtime_data.config;
+    let (width, mut height) = window_size(config, &runtime_data.geometry);
+    let anchors = anchors(config, &runtime_data.geometry);
+
+    let sized: &gtk::Widget = if config.covers_monitor() {
+        window.add_css_class(COVERING_CLASS);
+        if runtime_data.layer_shell {
+            anchor_window(
+                window,
+                &[
+                    (Edge::Left, 0),
+                    (Edge::Right, 0),
+                    (Edge::Top, 0),
+                    (Edge::Bottom, 0),
+                ],
+            );
+        } else {
+            window.fullscreen();
+        }
+        anchor_content(content, &anchors);
+        // The default height of the window doesn't reach the content
+        if height == Some(0) {
+            height = default_height(config);
+        }
+        content
+    } else {
+        // Regular windows are placed by the compositor
+        if runtime_data.layer_shell {
+            anchor_window(window, &anchors);
+        }
+        window.upcast_ref()
+    };
+
+    sized.set_width_request(width);
+    if let Some(height) = height {
+        sized.set_height_request(height);
+    }
+}
+
+/// The width and height of the window on a monitor of the size. There is no height with
+/// `max_height` set, as the height follows the content then.
+fn window_size(config: &Config, geometry: &gdk::Rectangle) -> (i32, Option<i32>) {
+    let width = config.width.to_val(geometry.width().try_into().unwrap());
+    let height = match config.max_height {
+        Some(_) => None,
+        None => Some(config.height.to_val(geometry.height().try_into().unwrap())),
+    };
+    (width, height)
+}
+
+/// The height of the window unless `height` says otherwise. There is none with `max_height` set,
+/// as the height follows the content then, and the bar is a single line.
+fn default_height(config: &Config) -> Option<i32> {
+    if config.max_height.is_some() || config.layout == Layout::Bar {
+        None
+    } else {
+        Some(500)
+    }
+}
+
+/// The edges the window is anchored to for the configured position on a monitor of the size,
+/// with the margins from them
+fn anchors(config: &Config, geometry: &gdk::Rectangle) -> Vec<(Edge, i32)> {
+    let width = geometry.width().try_into().unwrap();
+    let height = geometry.height().try_into().unwrap();
+
+    match config.position {
+        Position::Edges => config
+            .edges
+            .iter()
+            .enumerate()
+            .map(|(i, edge)| {
+                let margin = config
+                    .margin
+                    .get(i)
+                    .unwrap_or(&RelativeNum::default())
+                    .to_val(match edge {
+                        Edge::Left | Edge::Right => width,
+                        Edge::Top | Edge::Bottom => height,
+                    });
+                (*edge, margin)
+            })
+            .collect(),
+        // Without anchors layer surfaces are centered, and the content is centered like them
+        // until the pointer is seen
+        Position::Center | Position::Pointer => Vec::new(),
+        Position::Top(fraction) => {
+            vec![(Edge::Top, RelativeNum::Fraction(fraction).to_val(height))]
+        }
+    }
+}
+
+fn anchor_window(window: &impl GtkWindowExt, anchors: &[(Edge, i32)]) {
+    for edge in [Edge::Left, Edge::Right, Edge::Top, Edge::Bottom] {
+        let margin = anchor_margin(anchors, edge);
+        window.set_anchor(edge.into(), margin.is_some());
+        window.set_margin(edge.into(), margin.unwrap_or(0));
+    }
+}
+
+/// Aligns the content inside a window covering the monitor like the anchors would align the
+/// window on the monitor
+fn anchor_content(content: &impl WidgetExt, anchors: &[(Edge, i32)]) {
+    let align = |start: Option<i32>, end: Option<i32>| match (start, end) {
+        (Some(_), Some(_)) => gtk::Align::Fill,
+        (Some(_), None) => gtk::Align::Start,
+        (None, Some(_)) => gtk::Align::End,
+        (None, None) => gtk::Align::Center,
+    };
+
+    let left = anchor_margin(anchors, Edge::Left);
+    let right = anchor_margin(anchors, Edge::Right);
+    let top = anchor_margin(anchors, Edge::Top);
+    let bottom = anchor_margin(anchors, Edge::Bottom);
+
+    content.set_halign(align(left, right));
+    content.set_valign(align(top, bottom));
+    content.set_margin_start(left.unwrap_or(0));
+    content.set_margin_end(right.unwrap_or(0));
+    content.set_margin_top(top.unwrap_or(0));
+    content.set_margin_bottom(bottom.unwrap_or(0));
+}
+
+fn anchor_margin(anchors: &[(Edge, i32)], edge: Edge) -> Option<i32> {
+    anchors
+        .iter()
+        .find(|(anchor, _)| *anchor == edge)
+        .map(|(_, margin)| *margin)
+}
+
+/// The class of the window while it covers the monitor
+const COVERING_CLASS: &str = "covering";
+
+/// The look of the window covering the monitor unless the user's CSS says otherwise, as only
+/// the content should be seen
+const DEFAULT_CSS: &str = "window.covering { background: none; }
+window.covering box#main { background-color: @window_bg_color; border-radius: 12px; padding: 12px; }";
+
 pub fn load_custom_css(runtime_data: Rc<RefCell<RuntimeData>>) {
-    let config_dir = &runtime_data.borrow().config_dir;
-    let css_path = config_dir.join("style.css");
+    let display = gdk::Display::default().expect("Failed to get GDK display for CSS provider!");
+
+    let default_provider = gtk::CssProvider::new();
+    default_provider.load_from_data(&format!("{}\n{}", highlight::DEFAULT_CSS, DEFAULT_CSS));
+    gtk::style_context_add_provider_for_display(
+        &display,
+        &default_provider,
+        gtk::STYLE_PROVIDER_PRIORITY_FALLBACK,
+    );
+
+    let theme = runtime_data.borrow().config.theme;
+    theme::load_theme(&display, theme);
+
+    let css_path = runtime_data.borrow().config_dir.join("style.css");
 
     if fs::metadata(&css_path).is_ok() {
         info!("Applying custom CSS from {:?}", css_path);
         let provider = gtk::CssProvider::new();
         provider.load_from_path(css_path);
 
-        let display = gdk::Display::default().expect("Failed to get GDK display for CSS provider!");
         gtk::style_context_add_provider_for_display(
             &display,
             &provider,
             gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
         );
     }
+
+    // Resolving the look of highlights takes styling a label, so it's done once the CSS is loaded
+    // and again when the theme changes, rather than for every refresh of the matches
+    runtime_data.borrow_mut().highlight_attributes = highlight::highlight_attributes();
+    if let Some(settings) = gtk::Settings::default() {
+        for property in ["gtk-theme-name", "gtk-application-prefer-dark-theme"] {
+            settings.connect_notify_local(
+                Some(property),
+                glib::clone!(@weak runtime_data => move |_, _| {
+                    runtime_data.borrow_mut().highlight_attributes =
+                        highlight::highlight_attributes();
+                }),
+            );
+        }
+    }
 }
 
 fn connect_key_press_events<F>(
@@ -88,37 +356,81 @@ fn connect_key_press_events<F>(
     event_controller_key: gtk::EventControllerKey,
     handler: F,
 ) where
-    F: Fn(Key) -> glib::Propagation + 'static,
+    F: Fn(Key, gdk::ModifierType) -> glib::Propagation + 'static,
 {
     widget.add_controller(event_controller_key.clone());
-    event_controller_key.connect_key_pressed(move |_, keyval, _, _| handler(keyval));
+    event_controller_key
+        .connect_key_pressed(move |_, keyval, _, modifiers| handler(keyval, modifiers));
 }
 
-pub fn connect_window_key_press_events(
+/// `on_back` is called on `Escape` and `Alt+Left`, and should return `false` if there was
+/// no submenu to go back from, in which case `Escape` closes the window.
+pub fn connect_window_key_press_events<B>(
     widget: Rc<impl WidgetExt>,
     event_controller_key: gtk::EventControllerKey,
     window: Rc<impl GtkWindowExt>,
-) {
-    connect_key_press_events(widget, event_controller_key, move |keyval| match keyval {
-        Key::Escape => {
-            window.close();
-            glib::Propagation::Stop
-        }
-        _ => glib::Propagation::Proceed,
-    });
+    on_back: B,
+) where
+    B: Fn() -> bool + 'static,
+{
+    connect_key_press_events(
+        widget,
+        event_controller_key,
+        move |keyval, modifiers| match keyval {
+            Key::Left if modifiers.contains(gdk::ModifierType::ALT_MASK) => {
+                on_back();
+                glib::Propagation::Stop
+            }
+            Key::Escape => {
+                if !on_back() {
+                    window.close();
+                }
+                glib::Propagation::Stop
+            }
+            _ => glib::Propagation::Proceed,
+        },
+    );
 }
 
-pub fn connect_entry_key_press_events(
-    widget: Rc<impl WidgetExt>,
+/// `on_back` behaves like in [`connect_window_key_press_events`]. `on_up` is called on `Backspace`
+/// in an empty entry or on `Alt+Backspace`, and should return `false` if there was neither a
+/// submenu nor an exclusive mode to leave. `on_complete` is called on `Tab`, and should return
+/// `false` if nothing was completed so the focus can move on.
+pub fn connect_entry_key_press_events<B, U, C>(
+    widget: Rc<impl WidgetExt + EditableExt>,
     event_controller_key: gtk::EventControllerKey,
     window: Rc<impl GtkWindowExt>,
-) {
+    on_back: B,
+    on_up: U,
+    on_complete: C,
+) where
+    B: Fn() -> bool + 'static,
+    U: Fn() -> bool + 'static,
+    C: Fn() -> bool + 'static,
+{
+    // The inner text widget consumes `Backspace` itself, so we need to see it first
+    event_controller_key.set_propagation_phase(gtk::PropagationPhase::Capture);
+
     connect_key_press_events(
         widget.clone(),
         event_controller_key,
-        move |keyval| match keyval {
+        move |keyval, modifiers| match keyval {
+            Key::BackSpace
+                if (widget.text().is_empty()
+                    || modifiers.contains(gdk::ModifierType::ALT_MASK))
+                    && on_up() =>
+            {
+                glib::Propagation::Stop
+            }
+            Key::Tab if on_complete() => glib::Propagation::Stop,
+            Key::Left if modifiers.contains(gdk::ModifierType::ALT_MASK) => {
+                on_back();
+                glib::Propagation::Stop
+            }
             Key::Escape => {
-                window.close();
+                if !on_back() {
+                    window.close();
+                }
                 glib::Propagation::Stop
             }
             Key::Down | Key::Up => {
@@ -134,14 +446,367 @@ pub fn connect_entry_key_press_events(
     );
 }
 
-pub fn handle_selection_activation<F>(
-    row_id: usize,
-    window: Rc<impl GtkWindowExt>,
-    runtime_data: Rc<RefCell<RuntimeData>>,
-    mut on_refresh: F,
+pub fn build_exclusive_chip() -> gtk::Box {
+    gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .name(style_names::EXCLUSIVE)
+        .valign(gtk::Align::Center)
+        .spacing(4)
+        .visible(false)
+        .build()
+}
+
+/// Shows the icon and name of the exclusive plugin in the chip followed by the titles of the
+/// entered submenus, or hides it if there is no exclusive plugin
+pub fn update_exclusive_chip(chip: &gtk::Box, runtime_data: Rc<RefCell<RuntimeData>>) {
+    while let Some(child) = chip.first_child() {
+        chip.remove(&child);
+    }
+
+    let runtime_data = runtime_data.borrow();
+
+    let Some(plugin) = runtime_data.exclusive_plugin() else {
+        chip.set_visible(false);
+        return;
+    };
+
+    let plugin_info = plugin.info()();
+
+    chip.append(&build_image(&[&plugin_info.icon], 16));
+    chip.append(&gtk::Label::new(Some(&plugin_info.name)));
+
+    for level in &runtime_data.nav_stack {
+        chip.append(&gtk::Label::new(Some("›")));
+        chip.append(
+            &gtk::Label::builder()
+                .name(style_names::BREADCRUMB)
+                .label(&level.title)
+                .build(),
+        );
+    }
+
+    chip.set_visible(true);
+}
+
+pub fn build_confirm_bar() -> gtk::Box {
+    gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .name(style_names::CONFIRM)
+        .visible(false)
+        .build()
+}
+
+/// Shows the prompt with cancel and confirm buttons in the confirm bar, with the rest of the
+/// layout disabled until it is answered. `on_answer` is called with `true` on confirm and with
+/// `false` on cancel or `Escape`.
+pub fn show_confirm_bar<F>(
+    confirm_bar: &gtk::Box,
+    prompt: &str,
+    confirm_label: Option<&str>,
+    on_answer: F,
 ) where
-    F: FnMut(bool),
+    F: Fn(bool) + 'static,
+{
+    clear_confirm_bar(confirm_bar);
+
+    let on_answer = Rc::new(on_answer);
+
+    let cancel_button = gtk::Button::with_label("Cancel");
+    cancel_button.connect_clicked(glib::clone!(@strong on_answer => move |_| on_answer(false)));
+
+    let confirm_button = gtk::Button::with_label(confirm_label.unwrap_or("Confirm"));
+    confirm_button.add_css_class("destructive-action");
+    confirm_button.connect_clicked(glib::clone!(@strong on_answer => move |_| on_answer(true)));
+
+    // Rebuilt for every prompt, so no key handler of an earlier one is left behind
+    let hbox = Rc::new(
+        gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .hexpand(true)
+            .spacing(8)
+            .build(),
+    );
+    hbox.append(
+        &gtk::Label::builder()
+            .label(prompt)
+            .hexpand(true)
+            .xalign(0.0)
+            .wrap(true)
+            .build(),
+    );
+    hbox.append(&cancel_button);
+    hbox.append(&confirm_button);
+
+    connect_key_press_events(
+        hbox.clone(),
+        gtk::EventControllerKey::new(),
+        move |keyval, _| match keyval {
+            Key::Escape => {
+                on_answer(false);
+                glib::Propagation::Stop
+            }
+            _ => glib::Propagation::Proceed,
+        },
+    );
+
+    confirm_bar.append(&*hbox);
+    set_siblings_sensitive(confirm_bar, false);
+    confirm_bar.set_visible(true);
+    confirm_button.grab_focus();
+}
+
+pub fn hide_confirm_bar(confirm_bar: &gtk::Box) {
+    clear_confirm_bar(confirm_bar);
+    confirm_bar.set_visible(false);
+    set_siblings_sensitive(confirm_bar, true);
+}
+
+fn clear_confirm_bar(confirm_bar: &gtk::Box) {
+    while let Some(child) = confirm_bar.first_child() {
+        confirm_bar.remove(&child);
+    }
+}
+
+/// Disables or enables everything next to the widget, so only it can be interacted with
+fn set_siblings_sensitive(widget: &impl IsA<gtk::Widget>, sensitive: bool) {
+    let widget = widget.upcast_ref::<gtk::Widget>();
+    let mut sibling = widget.parent().and_then(|parent| parent.first_child());
+    while let Some(other) = sibling {
+        if other != *widget {
+            other.set_sensitive(sensitive);
+        }
+        sibling = other.next_sibling();
+    }
+}
+
+pub fn build_form_view() -> gtk::Box {
+    gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .name(style_names::FORM)
+        .build()
+}
+
+/// Shows the form in place of the matches, with the rest of the layout disabled until it is
+/// submitted. `on_answer` is called with the values of the fields on submit or `Enter`, and with
+/// `None` on cancel or `Escape`.
+pub fn show_form<F>(
+    form_view: &gtk::Box,
+    title: &str,
+    fields: &[FormField],
+    submit_label: Option<&str>,
+    on_answer: F,
+) where
+    F: Fn(Option<Vec<FormValue>>) + 'static,
 {
+    while let Some(child) = form_view.first_child() {
+        form_view.remove(&child);
+    }
+
+    // Rebuilt for every form, so no key handler of an earlier one is left behind
+    let vbox = Rc::new(
+        gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(8)
+            .build(),
+    );
+    vbox.append(
+        &gtk::Label::builder()
+            .label(title)
+            .xalign(0.0)
+            .wrap(true)
+            .build(),
+    );
+
+    let grid = gtk::Grid::builder()
+        .row_spacing(8)
+        .column_spacing(8)
+        .build();
+
+    // Pairs of field name and a getter for its current value
+    let mut values: Vec<(String, Box<dyn Fn() -> String>)> = Vec::new();
+    let mut text_entries = Vec::new();
+
+    for (row, field) in fields.iter().enumerate() {
+        let row = row as i32;
+        grid.attach(
+            &gtk::Label::builder()
+                .label(field.label.as_str())
+                .xalign(0.0)
+                .build(),
+            0,
+            row,
+            1,
+            1,
+        );
+
+        let input: gtk::Widget = match &field.kind {
+            FormFieldKind::Text { placeholder } => {
+                let text_entry = gtk::Entry::builder()
+                    .text(field.default.as_str())
+                    .placeholder_text(placeholder.as_str())
+                    .hexpand(true)
+                    .build();
+                values.push((
+                    field.name.to_string(),
+                    Box::new(glib::clone!(@strong text_entry => move || {
+                        text_entry.text().into()
+                    })),
+                ));
+                text_entries.push(text_entry.clone());
+                text_entry.upcast()
+            }
+            FormFieldKind::Choice { choices } => {
+                let choices: Vec<&str> = choices.iter().map(|choice| choice.as_str()).collect();
+                let drop_down = gtk::DropDown::from_strings(&choices);
+                drop_down.set_hexpand(true);
+                if let Some(position) = choices
+                    .iter()
+                    .position(|choice| *choice == field.default.as_str())
+                {
+                    drop_down.set_selected(position as u32);
+                }
+                values.push((
+                    field.name.to_string(),
+                    Box::new(glib::clone!(@strong drop_down => move || {
+                        drop_down
+                            .selected_item()
+                            .and_downcast::<gtk::StringObject>()
+                            .map(|choice| choice.string().into())
+                            .unwrap_or_default()
+                    })),
+                ));
+                drop_down.upcast()
+            }
+        };
+        grid.attach(&input, 1, row, 1, 1);
+    }
+    vbox.append(&grid);
+
+    let on_answer = Rc::new(on_answer);
+    let submit = Rc::new(glib::clone!(@strong on_answer => move || {
+        on_answer(Some(
+            values
+                .iter()
+                .map(|(name, value)| FormValue {
+                    name: name.as_str().into(),
+                    value: value().into(),
+                })
+                .collect(),
+        ))
+    }));
+
+    let cancel_button = gtk::Button::with_label("Cancel");
+    cancel_button.connect_clicked(glib::clone!(@strong on_answer => move |_| on_answer(None)));
+
+    let submit_button = gtk::Button::with_label(submit_label.unwrap_or("Submit"));
+    submit_button.add_css_class("suggested-action");
+    submit_button.connect_clicked(glib::clone!(@strong submit => move |_| submit()));
+
+    for text_entry in &text_entries {
+        text_entry.connect_activate(glib::clone!(@strong submit => move |_| submit()));
+    }
+
+    let button_hbox = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .halign(gtk::Align::End)
+        .spacing(8)
+        .build();
+    button_hbox.append(&cancel_button);
+    button_hbox.append(&submit_button);
+
+    vbox.append(&button_hbox);
+
+    connect_key_press_events(
+        vbox.clone(),
+        gtk::EventControllerKey::new(),
+        move |keyval, _| match keyval {
+            Key::Escape => {
+                on_answer(None);
+                glib::Propagation::Stop
+            }
+            _ => glib::Propagation::Proceed,
+        },
+    );
+
+    form_view.append(&*vbox);
+
+    if let Some(stack) = form_view.parent().and_downcast::<gtk::Stack>() {
+        stack.set_visible_child(form_view);
+        set_siblings_sensitive(&stack, false);
+    }
+
+    match text_entries.first() {
+        Some(text_entry) => text_entry.grab_focus(),
+        None => submit_button.grab_focus(),
+    };
+}
+
+pub fn hide_form(form_view: &gtk::Box) {
+    if let Some(stack) = form_view.parent().and_downcast::<gtk::Stack>() {
+        stack.set_visible_child_name(MATCHES_PAGE);
+        set_siblings_sensitive(&stack, true);
+    }
+
+    while let Some(child) = form_view.first_child() {
+        form_view.remove(&child);
+    }
+}
+
+/// Goes back to the previous navigation level, returns `false` if there is none
+pub fn navigate_back(
+    runtime_data: Rc<RefCell<RuntimeData>>,
+    entry: Rc<gtk::SearchEntry>,
+    exclusive_chip: &gtk::Box,
+    plugins: &[Plugin],
+) -> bool {
+    let Some(level) = runtime_data.borrow_mut().nav_stack.pop() else {
+        return false;
+    };
+
+    let plugin = runtime_data.borrow().exclusive_plugin();
+    if let Some(plugin) = plugin {
+        plugin.navigate_back()();
+    }
+
+    {
+        let mut runtime_data = runtime_data.borrow_mut();
+        runtime_data.exclusive = level.exclusive;
+        runtime_data.pending_selection = level.selection;
+    }
+    update_exclusive_chip(exclusive_chip, runtime_data.clone());
+
+    if entry.text() == level.input {
+        refresh_matches(&level.input, plugins, runtime_data);
+    } else {
+        entry.set_text(&level.input);
+        entry.set_position(-1);
+    }
+    true
+}
+
+/// Goes up one level: back from a submenu, or out of the exclusive mode.
+/// Returns `false` if neither was possible.
+pub fn navigate_up(
+    runtime_data: Rc<RefCell<RuntimeData>>,
+    entry: Rc<gtk::SearchEntry>,
+    exclusive_chip: &gtk::Box,
+    plugins: &[Plugin],
+) -> bool {
+    if navigate_back(runtime_data.clone(), entry.clone(), exclusive_chip, plugins) {
+        return true;
+    }
+    if runtime_data.borrow().exclusive.is_none() {
+        return false;
+    }
+
+    runtime_data.borrow_mut().exclusive = None;
+    update_exclusive_chip(exclusive_chip, runtime_data.clone());
+    refresh_matches(&entry.text(), plugins, runtime_data);
+    true
+}
+
+/// The match at `row_id`, with the index of its plugin and the plugin
+fn get_match(row_id: usize, runtime_data: Rc<RefCell<RuntimeData>>) -> (Match, usize, Plugin) {
     let gmatch = runtime_data
         .borrow()
         .list_store
@@ -150,23 +815,470 @@ pub fn handle_selection_activation<F>(
         .downcast::<GMatch>()
         .expect("Failed to downcast Object to MatchRow");
 
-    let rmatch: Match = gmatch.clone().into();
+    let plugin_id = gmatch.get_plugin_id() as usize;
     let plugin = *runtime_data
         .borrow()
         .plugins
-        .get(gmatch.get_plugin_id() as usize)
+        .get(plugin_id)
         .expect("Can't get plugin");
 
-    match plugin.handle_selection()(rmatch) {
+    (gmatch.into(), plugin_id, plugin)
+}
+
+fn set_entry_text(entry: &impl EditableExt, entry_text: EntryText) {
+    entry.set_text(&entry_text.text);
+    entry.set_position(
+        entry_text
+            .cursor
+            .into_option()
+            .and_then(|cursor| cursor.try_into().ok())
+            .unwrap_or(-1),
+    );
+}
+
+/// Asks the plugin of the match to complete the entry text, returns `false` if it didn't
+pub fn handle_completion(
+    row_id: usize,
+    entry: &impl EditableExt,
+    runtime_data: Rc<RefCell<RuntimeData>>,
+) -> bool {
+    let (rmatch, _, plugin) = get_match(row_id, runtime_data);
+
+    match plugin.complete()(rmatch).into_option() {
+        Some(entry_text) => {
+            set_entry_text(entry, entry_text);
+            true
+        }
+        None => false,
+    }
+}
+
+pub fn build_preview() -> gtk::ScrolledWindow {
+    gtk::ScrolledWindow::builder()
+        .name(style_names::PREVIEW)
+        .hexpand(true)
+        .vexpand(true)
+        .focusable(false)
+        .visible(false)
+        .build()
+}
+
+/// Shows the preview of the match at `row_id` provided by its plugin, or hides the preview pane
+/// if there is none
+pub fn update_preview(
+    preview: &gtk::ScrolledWindow,
+    row_id: Option<usize>,
+    runtime_data: Rc<RefCell<RuntimeData>>,
+) {
+    let content = row_id.and_then(|row_id| {
+        let (rmatch, _, plugin) = get_match(row_id, runtime_data);
+        plugin.preview()(rmatch).into_option()
+    });
+
+    match content {
+        Some(content) => {
+            preview.set_child(Some(&build_preview_content(content)));
+            preview.set_visible(true);
+        }
+        None => {
+            preview.set_child(gtk::Widget::NONE);
+            preview.set_visible(false);
+        }
+    }
+}
+
+fn build_preview_content(content: Preview) -> gtk::Widget {
+    match content {
+        Preview::Markup(markup) => gtk::Label::builder()
+            .label(markup.as_str())
+            .use_markup(is_valid_markup(&markup))
+            .wrap(true)
+            .xalign(0.0)
+            .yalign(0.0)
+            .build()
+            .upcast(),
+        Preview::Image(path) => {
+            let picture = gtk::Picture::builder().can_shrink(true).build();
+            glib::MainContext::default()
+                .spawn_local(load_preview_image(picture.downgrade(), path.into()));
+            picture.upcast()
+        }
+        Preview::File(path) => {
+            let buffer = gtk::TextBuffer::new(None);
+            glib::MainContext::default()
+                .spawn_local(load_preview_file(buffer.downgrade(), path.into()));
+
+            gtk::TextView::builder()
+                .buffer(&buffer)
+                .editable(false)
+                .cursor_visible(false)
+                .focusable(false)
+                .monospace(true)
+                .build()
+                .upcast()
+        }
+    }
+}
+
+/// Shows the image in the picture once it's loaded, if the picture is still around by then
+async fn load_preview_image(picture: glib::WeakRef<gtk::Picture>, path: String) {
+    let pixbuf = match gio::File::for_path(&path)
+        .read_future(glib::Priority::DEFAULT)
+        .await
+    {
+        Ok(stream) => Pixbuf::from_stream_future(&stream).await,
+        Err(why) => Err(why),
+    };
+
+    match pixbuf {
+        Ok(pixbuf) => {
+            if let Some(picture) = picture.upgrade() {
+                picture.set_paintable(Some(&gdk::Texture::for_pixbuf(&pixbuf)));
+            }
+        }
+        Err(why) => warn!("Failed to load {} for its preview: {}", path, why),
+    }
+}
+
+/// Shows the start of the file in the buffer once it's read, if the buffer is still around by then
+async fn load_preview_file(buffer: glib::WeakRef<gtk::TextBuffer>, path: String) {
+    let bytes = match gio::File::for_path(&path)
+        .read_future(glib::Priority::DEFAULT)
+        .await
+    {
+        Ok(stream) => {
+            stream
+                .read_bytes_future(PREVIEW_FILE_LIMIT, glib::Priority::DEFAULT)
+                .await
+        }
+        Err(why) => Err(why),
+    };
+
+    let text = match bytes {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(why) => {
+            warn!("Failed to read {} for its preview: {}", path, why);
+            why.to_string()
+        }
+    };
+    if let Some(buffer) = buffer.upgrade() {
+        buffer.set_text(&text);
+    }
+}
+
+pub fn modifiers_from_state(state: gdk::ModifierType) -> Modifiers {
+    Modifiers {
+        shift: state.contains(gdk::ModifierType::SHIFT_MASK),
+        ctrl: state.contains(gdk::ModifierType::CONTROL_MASK),
+        alt: state.contains(gdk::ModifierType::ALT_MASK),
+        super_key: state.contains(gdk::ModifierType::SUPER_MASK),
+    }
+}
+
+/// The modifiers currently held on the keyboard, for activations without a key event at hand
+pub fn keyboard_modifiers(widget: &impl WidgetExt) -> Modifiers {
+    modifiers_from_state(
+        widget
+            .display()
+            .default_seat()
+            .and_then(|seat| seat.keyboard())
+            .map(|keyboard| keyboard.modifier_state())
+            .unwrap_or(gdk::ModifierType::empty()),
+    )
+}
+
+/// Asks the compositor for an xdg-activation token, only possible while the window is still open
+fn activation_token(window: &impl IsA<gtk::Window>, program: &str) -> Option<String> {
+    let app_info =
+        gio::AppInfo::create_from_commandline(program, None, gio::AppInfoCreateFlags::NONE).ok()?;
+
+    window
+        .upcast_ref::<gtk::Window>()
+        .display()
+        .app_launch_context()
+        .startup_notify_id(&app_info, &[])
+        .map(Into::into)
+}
+
+fn build_launch(command: LaunchCommand, activation_token: Option<String>) -> Launch {
+    Launch {
+        argv: command.argv.into_iter().map(Into::into).collect(),
+        working_dir: command
+            .working_dir
+            .into_option()
+            .map(|dir| dir.as_str().into()),
+        env: command
+            .env
+            .into_iter()
+            .map(|pair| (pair.0.into(), pair.1.into()))
+            .collect(),
+        terminal: command.terminal,
+        activation_token,
+    }
+}
+
+/// Lets the plugin of the match at `row_id` handle it. If the plugin asks for a confirmation
+/// it is stored in the runtime data for the caller to show.
+pub fn handle_selection_activation<F>(
+    row_id: usize,
+    activation: Activation,
+    window: Rc<impl GtkWindowExt>,
+    entry: &impl EditableExt,
+    runtime_data: Rc<RefCell<RuntimeData>>,
+    on_refresh: F,
+) where
+    F: FnMut(bool),
+{
+    let (rmatch, plugin_id, plugin) = get_match(row_id, runtime_data.clone());
+
+    handle_match_activation(
+        Selection {
+            row_id,
+            rmatch,
+            plugin_id,
+            plugin,
+            activation,
+        },
+        window,
+        entry,
+        runtime_data,
+        on_refresh,
+    );
+}
+
+/// Handles the selection of the pending confirmation again, after the user has confirmed it
+pub fn handle_confirmed_activation<F>(
+    window: Rc<impl GtkWindowExt>,
+    entry: &impl EditableExt,
+    runtime_data: Rc<RefCell<RuntimeData>>,
+    on_refresh: F,
+) where
+    F: FnMut(bool),
+{
+    let confirmation = runtime_data.borrow_mut().confirmation.take();
+    if let Some(confirmation) = confirmation {
+        handle_match_activation(
+            confirmation.selection,
+            window,
+            entry,
+            runtime_data,
+            on_refresh,
+        );
+    }
+}
+
+fn handle_match_activation<F>(
+    selection: Selection,
+    window: Rc<impl GtkWindowExt>,
+    entry: &impl EditableExt,
+    runtime_data: Rc<RefCell<RuntimeData>>,
+    on_refresh: F,
+) where
+    F: FnMut(bool),
+{
+    let plugin = selection.plugin;
+    let rmatch = selection.rmatch.clone();
+
+    let handle_result = plugin.handle_activation()(rmatch, selection.activation);
+
+    apply_handle_result(
+        handle_result,
+        selection,
+        window,
+        entry,
+        runtime_data,
+        on_refresh,
+    );
+}
+
+/// Passes the values of the submitted pending form to its plugin
+pub fn handle_form_submission<F>(
+    values: Vec<FormValue>,
+    window: Rc<impl GtkWindowExt>,
+    entry: &impl EditableExt,
+    runtime_data: Rc<RefCell<RuntimeData>>,
+    on_refresh: F,
+) where
+    F: FnMut(bool),
+{
+    let Some(form) = runtime_data.borrow_mut().form.take() else {
+        return;
+    };
+
+    let handle_result =
+        form.selection.plugin.handle_form()(form.selection.rmatch.clone(), values.into());
+
+    apply_handle_result(
+        handle_result,
+        form.selection,
+        window,
+        entry,
+        runtime_data,
+        on_refresh,
+    );
+}
+
+fn apply_handle_result<F>(
+    handle_result: HandleResult,
+    selection: Selection,
+    window: Rc<impl GtkWindowExt>,
+    entry: &impl EditableExt,
+    runtime_data: Rc<RefCell<RuntimeData>>,
+    mut on_refresh: F,
+) where
+    F: FnMut(bool),
+{
+    let Selection {
+        row_id,
+        plugin_id,
+        plugin,
+        activation,
+        ..
+    } = selection;
+
+    match handle_result {
         HandleResult::Close => window.close(),
         HandleResult::Refresh(exclusive) => {
-            runtime_data.borrow_mut().exclusive = if exclusive { Some(plugin) } else { None };
-            on_refresh(exclusive);
+            {
+                let mut runtime_data = runtime_data.borrow_mut();
+                runtime_data.exclusive = if exclusive { Some(plugin_id) } else { None };
+                if !exclusive {
+                    runtime_data.nav_stack.clear();
+                }
+            }
+            on_refresh(true);
+        }
+        HandleResult::Submenu(title) => {
+            let level = NavLevel {
+                title: title.into(),
+                exclusive: runtime_data.borrow().exclusive,
+                input: entry.text().into(),
+                selection: row_id.try_into().ok(),
+            };
+            {
+                let mut runtime_data = runtime_data.borrow_mut();
+                runtime_data.nav_stack.push(level);
+                runtime_data.exclusive = Some(plugin_id);
+            }
+            if entry.text().is_empty() {
+                on_refresh(true);
+            } else {
+                entry.set_text("");
+                on_refresh(false);
+            }
+        }
+        HandleResult::SetInput(entry_text) => {
+            // `changed` isn't emitted for the same text, which would refresh the matches
+            let refresh = entry.text() == entry_text.text.as_str();
+            set_entry_text(entry, entry_text);
+            on_refresh(refresh);
+        }
+        HandleResult::Launch(command) => {
+            let activation_token = command
+                .argv
+                .first()
+                .and_then(|program| activation_token(&*window, program));
+            runtime_data.borrow_mut().post_run_action =
+                PostRunAction::Launch(build_launch(command, activation_token));
+            window.close();
+        }
+        HandleResult::OpenUri(uri) => {
+            runtime_data.borrow_mut().post_run_action = PostRunAction::Open(Open {
+                target: OpenTarget::Uri(uri.into()),
+                activation_token: activation_token(&*window, "xdg-open"),
+            });
+            window.close();
+        }
+        HandleResult::OpenFile(file) => {
+            let path = file.path.as_str().into();
+            runtime_data.borrow_mut().post_run_action = PostRunAction::Open(Open {
+                target: if file.reveal {
+                    OpenTarget::Reveal(path)
+                } else {
+                    OpenTarget::File(path)
+                },
+                activation_token: activation_token(&*window, "xdg-open"),
+            });
+            window.close();
         }
         HandleResult::Copy(bytes) => {
-            runtime_data.borrow_mut().post_run_action = PostRunAction::Copy(bytes.into());
+            runtime_data.borrow_mut().post_run_action = PostRunAction::Copy(ClipboardCopy {
+                offers: vec![(None, bytes.into())],
+                clipboard: ClipboardType::Regular,
+                paste_limit: None,
+                timeout: None,
+            });
+            window.close();
+        }
+        HandleResult::CopyContent(content) => {
+            runtime_data.borrow_mut().post_run_action = PostRunAction::Copy(ClipboardCopy {
+                offers: content
+                    .offers
+                    .into_iter()
+                    .map(|offer| {
+                        (
+                            offer.mime_type.into_option().map(Into::into),
+                            offer.bytes.into(),
+                        )
+                    })
+                    .collect(),
+                clipboard: content.clipboard,
+                paste_limit: content
+                    .paste_limit
+                    .into_option()
+                    .map(|limit| limit as usize),
+                timeout: content
+                    .timeout
+                    .into_option()
+                    .map(|secs| Duration::from_secs(secs.into())),
+            });
+            window.close();
+        }
+        HandleResult::Notify(notification) => {
+            runtime_data.borrow_mut().post_run_action = PostRunAction::Notify(Notify {
+                summary: notification.summary.into(),
+                body: notification.body.into(),
+                icon: notification.icon.into_option().map(Into::into),
+                // The token comes from the notification server once an action is clicked
+                actions: notification
+                    .actions
+                    .into_iter()
+                    .map(|action| (action.label.into(), build_launch(action.command, None)))
+                    .collect(),
+                timeout: notification
+                    .timeout
+                    .into_option()
+                    .map(|millis| millis.try_into().unwrap_or(i32::MAX)),
+            });
             window.close();
         }
+        HandleResult::Confirm(confirmation) => {
+            runtime_data.borrow_mut().confirmation = Some(PendingConfirmation {
+                prompt: confirmation.prompt.into(),
+                confirm_label: confirmation.confirm_label.into_option().map(Into::into),
+                selection: Selection {
+                    row_id,
+                    rmatch: confirmation.selection,
+                    plugin_id,
+                    plugin,
+                    activation,
+                },
+            });
+        }
+        HandleResult::Form(form) => {
+            runtime_data.borrow_mut().form = Some(PendingForm {
+                title: form.title.into(),
+                fields: form.fields.into(),
+                submit_label: form.submit_label.into_option().map(Into::into),
+                selection: Selection {
+                    row_id,
+                    rmatch: form.selection,
+                    plugin_id,
+                    plugin,
+                    activation,
+                },
+            });
+        }
         HandleResult::Stdout(bytes) => {
             if let Err(why) = io::Write::write_all(&mut io::stdout().lock(), &bytes) {
                 error!("Error outputting content to stdout: {}", why);
@@ -177,34 +1289,34 @@ pub fn handle_selection_activation<F>(
 }
 
 pub fn configure_main_window(
-    window: Rc<impl WidgetExt + GtkWindowExt + NativeExt>,
+    window: Rc<impl IsA<gtk::Window> + WidgetExt + GtkWindowExt + NativeExt>,
     runtime_data: Rc<RefCell<RuntimeData>>,
     entry: Rc<impl WidgetExt>,
-    main_list: Rc<impl WidgetExt>,
+    exclusive_chip: Rc<gtk::Box>,
+    prompts: &Prompts,
+    preview: Rc<gtk::ScrolledWindow>,
+    results: &Results,
 ) {
     let runtime_data = runtime_data.borrow();
 
-    let width = runtime_data
-        .config
-        .width
-        .to_val(runtime_data.geometry.width().try_into().unwrap());
-    let height = runtime_data
-        .config
-        .height
-        .to_val(runtime_data.geometry.height().try_into().unwrap());
+    let (width, _) = window_size(&runtime_data.config, &runtime_data.geometry);
 
-    window.set_width_request(width);
-    window.set_height_request(height);
+    // The entry and the matches are put in a single line in the bar layout
+    let is_bar = runtime_data.config.layout == Layout::Bar;
 
     let main_vbox = gtk::Box::builder()
-        .orientation(gtk::Orientation::Vertical)
+        .orientation(if is_bar {
+            gtk::Orientation::Horizontal
+        } else {
+            gtk::Orientation::Vertical
+        })
         .halign(gtk::Align::Fill)
         .valign(gtk::Align::Fill)
         .name(style_names::MAIN)
         .margin_start(12)
         .margin_end(12)
-        .margin_top(12)
-        .margin_bottom(12)
+        .margin_top(if is_bar { 4 } else { 12 })
+        .margin_bottom(if is_bar { 4 } else { 12 })
         .spacing(12)
         .build();
 
@@ -220,22 +1332,201 @@ pub fn configure_main_window(
         );
     }
 
-    let scroll_window = gtk::ScrolledWindow::builder()
-        .vexpand(true)
-        .hexpand(true)
-        .focusable(false)
+    let stack = gtk::Stack::builder().vhomogeneous(false).build();
+    stack.add_named(&results.stack, Some(MATCHES_PAGE));
+    stack.add_child(&*prompts.form_view);
+
+    let content_box = gtk::Box::builder()
+        .orientation(match runtime_data.config.preview {
+            PreviewPosition::Bottom => gtk::Orientation::Vertical,
+            _ => gtk::Orientation::Horizontal,
+        })
+        .spacing(12)
         .build();
+    content_box.append(&stack);
+    if runtime_data.config.shows_preview() {
+        content_box.append(&*preview);
+    }
 
-    scroll_window.set_child(Some(&*main_list));
+    let entry_hbox = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .spacing(8)
+        .build();
+    entry_hbox.append(&*exclusive_chip);
+    entry_hbox.append(&*entry);
+
+    if is_bar {
+        // Leaves the rest of the line to the matches
+        entry.set_hexpand(false);
+        entry_hbox.set_width_request(width / 4);
+    }
 
     if runtime_data.config.bottom_entry {
-        main_vbox.append(&scroll_window);
-        main_vbox.append(&*entry);
+        main_vbox.append(&content_box);
+        main_vbox.append(&*prompts.confirm_bar);
+        main_vbox.append(&entry_hbox);
     } else {
-        main_vbox.append(&*entry);
-        main_vbox.append(&scroll_window);
+        main_vbox.append(&entry_hbox);
+        main_vbox.append(&*prompts.confirm_bar);
+        main_vbox.append(&content_box);
+    }
+
+    if let Some(status_bar) = &runtime_data.status_bar {
+        main_vbox.append(&status_bar.container);
     }
 
-    window.set_child(Some(&main_vbox));
+    match runtime_data.config.max_height {
+        Some(max_height) => {
+            let max_height = max_height.to_val(runtime_data.geometry.height().try_into().unwrap());
+            if runtime_data.config.covers_monitor() {
+                // Fitting the window doesn't reach the content when the window covers the
+                // monitor, so the content is capped by a scrolled window around it instead. The
+                // matches have a scrollbar of their own.
+                window.set_child(Some(
+                    &gtk::ScrolledWindow::builder()
+                        .hscrollbar_policy(gtk::PolicyType::Never)
+                        .vscrollbar_policy(gtk::PolicyType::External)
+                        .propagate_natural_width(true)
+                        .propagate_natural_height(true)
+                        .max_content_height(max_height)
+                        .child(&main_vbox)
+                        .build(),
+                ));
+            } else {
+                window.set_child(Some(&main_vbox));
+            }
+            fit_window_height(
+                window.upcast_ref(),
+                &content_box,
+                &runtime_data.list_store,
+                max_height,
+            );
+        }
+        None => window.set_child(Some(&main_vbox)),
+    }
     entry.grab_focus();
 }
+
+/// How long the content keeps its height after the matches were cleared, so that the window
+/// doesn't shrink and grow again while the new ones stream in
+const HEIGHT_SETTLE_TIME: Duration = Duration::from_millis(150);
+
+/// Keeps the height of the window fitted to its content up to `max_height`, with the content
+/// hidden when there are no matches so that only the entry is left
+fn fit_window_height(
+    window: &gtk::Window,
+    content: &gtk::Box,
+    list_store: &gio::ListStore,
+    max_height: i32,
+) {
+    let fit = Rc::new(glib::clone!(@weak window => move || {
+        let for_width = if window.width() > 0 { window.width() } else { -1 };
+        let (_, natural, _, _) = window.measure(gtk::Orientation::Vertical, for_width);
+
+        let (width, _) = window.default_size();
+        window.set_default_size(width, natural.min(max_height));
+    }));
+
+    content.set_visible(list_store.n_items() > 0);
+    fit();
+
+    let settle: Rc<Cell<Option<glib::SourceId>>> = Rc::new(Cell::new(None));
+    list_store.connect_items_changed(glib::clone!(@weak content => move |list_store, _, _, _| {
+        if list_store.n_items() > 0 {
+            content.set_visible(true);
+            fit();
+            return;
+        }
+
+        // Held at its current height until the new matches had time to arrive
+        content.set_height_request(content.height());
+        if let Some(source) = settle.take() {
+            source.remove();
+        }
+        settle.set(Some(glib::timeout_add_local_once(
+            HEIGHT_SETTLE_TIME,
+            glib::clone!(@strong settle, @strong fit, @weak list_store => move || {
+                settle.set(None);
+                content.set_height_request(-1);
+                content.set_visible(list_store.n_items() > 0);
+                fit();
+            }),
+        )));
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor() -> gdk::Rectangle {
+        gdk::Rectangle::new(0, 0, 1920, 1080)
+    }
+
+    #[test]
+    fn the_size_follows_the_content_with_max_height() {
+        let mut config = Config {
+            width: RelativeNum::Fraction(0.5),
+            height: RelativeNum::Absolute(400),
+            ..Config::default()
+        };
+        assert_eq!(window_size(&config, &monitor()), (960, Some(400)));
+
+        config.max_height = Some(RelativeNum::Fraction(0.5));
+        assert_eq!(window_size(&config, &monitor()), (960, None));
+        assert_eq!(default_height(&config), None);
+    }
+
+    #[test]
+    fn edges_are_anchored_with_their_margins() {
+        let config = Config {
+            position: Position::Edges,
+            edges: vec![Edge::Top, Edge::Left],
+            margin: vec![RelativeNum::Fraction(0.1), RelativeNum::Absolute(20)],
+            ..Config::default()
+        };
+        assert_eq!(
+            anchors(&config, &monitor()),
+            vec![(Edge::Top, 108), (Edge::Left, 20)]
+        );
+
+        // Edges without a margin are anchored right at them
+        let config = Config {
+            edges: vec![Edge::Bottom, Edge::Right],
+            margin: vec![RelativeNum::Absolute(8)],
+            ..config
+        };
+        assert_eq!(
+            anchors(&config, &monitor()),
+            vec![(Edge::Bottom, 8), (Edge::Right, 0)]
+        );
+    }
+
+    #[test]
+    fn top_is_offset_by_a_fraction_of_the_height() {
+        let config = Config {
+            position: Position::Top(0.25),
+            ..Config::default()
+        };
+        assert_eq!(anchors(&config, &monitor()), vec![(Edge::Top, 270)]);
+    }
+
+    #[test]
+    fn centered_positions_have_no_anchors() {
+        for position in [Position::Center, Position::Pointer] {
+            let config = Config {
+                position,
+                ..Config::default()
+            };
+            assert!(anchors(&config, &monitor()).is_empty());
+        }
+    }
+
+    #[test]
+    fn margins_are_looked_up_by_edge() {
+        let anchors = [(Edge::Left, 4), (Edge::Top, 0)];
+        assert_eq!(anchor_margin(&anchors, Edge::Left), Some(4));
+        assert_eq!(anchor_margin(&anchors, Edge::Top), Some(0));
+        assert_eq!(anchor_margin(&anchors, Edge::Right), None);
+    }
+}